@@ -0,0 +1,325 @@
+//! Decoding of revert data into the errors raised by Solidity contracts.
+use super::common;
+use ethereum_types::*;
+use std::error;
+use std::fmt;
+
+/// Selector of `Error(string)`, raised by `require` and `revert("...")`.
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`, raised by failing `assert`s and checked
+/// arithmetic.
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Name of a Solidity panic code.
+pub fn panic_name(code: U256) -> Option<&'static str> {
+    if code > U256::from(0xff) {
+        return None;
+    }
+    match code.low_u64() {
+        0x00 => Some("generic compiler panic"),
+        0x01 => Some("assertion failed"),
+        0x11 => Some("arithmetic overflow or underflow"),
+        0x12 => Some("division or modulo by zero"),
+        0x21 => Some("invalid enum value"),
+        0x22 => Some("invalid storage byte array encoding"),
+        0x31 => Some("pop on empty array"),
+        0x32 => Some("array index out of bounds"),
+        0x41 => Some("out of memory"),
+        0x51 => Some("call to zero-initialized function"),
+        _ => None,
+    }
+}
+
+/// A decoded ABI value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    Uint(U256),
+    /// Two's complement, sign extended to 256 bits.
+    Int(U256),
+    Address(Address),
+    Bool(bool),
+    FixedBytes(Vec<u8>),
+    Bytes(Vec<u8>),
+    String(String),
+}
+
+/// Why a call reverted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevertReason {
+    Error(String),
+    Panic {
+        code: U256,
+        name: Option<&'static str>,
+    },
+    /// A custom error declared in the ABI, with its decoded arguments.
+    Custom {
+        signature: String,
+        args: Vec<Token>,
+    },
+    /// Revert data that matches no known error, including empty data.
+    Unknown(Vec<u8>),
+}
+
+/// A custom error declaration such as `InsufficientBalance(uint256,uint256)`.
+struct CustomError {
+    signature: String,
+    selector: [u8; 4],
+    params: Vec<String>,
+}
+
+/// Error returned when a custom error signature is not canonical or uses a
+/// parameter type the decoder does not support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSignature(pub String);
+
+impl fmt::Display for InvalidSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid error signature: {}", self.0)
+    }
+}
+
+impl error::Error for InvalidSignature {}
+
+/// The custom errors a contract may revert with. Errors are given by their
+/// canonical signature, as found in the ABI: the name followed by the
+/// parameter types without spaces or names. Arrays and tuples are not
+/// supported.
+#[derive(Default)]
+pub struct ErrorAbi {
+    errors: Vec<CustomError>,
+}
+
+impl ErrorAbi {
+    pub fn new(signatures: &[&str]) -> Result<Self, InvalidSignature> {
+        let mut abi = ErrorAbi::default();
+        for signature in signatures {
+            abi.add(signature)?;
+        }
+        Ok(abi)
+    }
+
+    /// Fails on anything but a canonical signature, such as `Foo` or
+    /// `Foo(uint256 amount)`, whose selector would never match.
+    pub fn add(&mut self, signature: &str) -> Result<(), InvalidSignature> {
+        let invalid = || InvalidSignature(signature.to_string());
+        let open = signature.find('(').ok_or_else(invalid)?;
+        if !is_identifier(&signature[..open]) || !signature.ends_with(')') {
+            return Err(invalid());
+        }
+        let list = &signature[open + 1..signature.len() - 1];
+        let params: Vec<String> = if list.is_empty() {
+            Vec::new()
+        } else {
+            list.split(',').map(String::from).collect()
+        };
+        if !params.iter().all(|p| is_supported_type(p)) {
+            return Err(invalid());
+        }
+        let mut selector = [0u8; 4];
+        selector.copy_from_slice(&common::keccak(signature.as_bytes())[..4]);
+        self.errors.push(CustomError {
+            signature: signature.to_string(),
+            selector,
+            params,
+        });
+        Ok(())
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Whether `decode` handles `ty`, written as in a canonical signature.
+fn is_supported_type(ty: &str) -> bool {
+    // Sizes are plain decimal numbers without leading zeros.
+    let size = |n: &str| -> Option<usize> {
+        if n.is_empty() || n.starts_with('0') || !n.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        n.parse().ok()
+    };
+    let bits = |n: &str| size(n).filter(|n| *n <= 256 && n % 8 == 0).is_some();
+    match ty {
+        "address" | "bool" | "bytes" | "string" => true,
+        _ if ty.starts_with("uint") => bits(&ty[4..]),
+        _ if ty.starts_with("int") => bits(&ty[3..]),
+        _ if ty.starts_with("bytes") => size(&ty[5..]).filter(|n| *n <= 32).is_some(),
+        _ => false,
+    }
+}
+
+/// Decodes revert data as `Error(string)`, `Panic(uint256)` or one of the
+/// custom errors in `abi`.
+pub fn decode_revert(data: &[u8], abi: &ErrorAbi) -> RevertReason {
+    if data.len() < 4 {
+        return RevertReason::Unknown(data.to_vec());
+    }
+    let (selector, args) = data.split_at(4);
+    if selector == ERROR_SELECTOR {
+        if let Some(Token::String(reason)) = decode(&["string"], args).and_then(|mut t| t.pop()) {
+            return RevertReason::Error(reason);
+        }
+    } else if selector == PANIC_SELECTOR {
+        if let Some(Token::Uint(code)) = decode(&["uint256"], args).and_then(|mut t| t.pop()) {
+            return RevertReason::Panic {
+                code,
+                name: panic_name(code),
+            };
+        }
+    } else if let Some(error) = abi.errors.iter().find(|e| e.selector == selector) {
+        let params: Vec<&str> = error.params.iter().map(String::as_str).collect();
+        if let Some(args) = decode(&params, args) {
+            return RevertReason::Custom {
+                signature: error.signature.clone(),
+                args,
+            };
+        }
+    }
+    RevertReason::Unknown(data.to_vec())
+}
+
+/// Decodes ABI-encoded values of the given types, or returns None if the
+/// data is malformed or a type is unsupported.
+pub fn decode(types: &[&str], data: &[u8]) -> Option<Vec<Token>> {
+    let mut tokens = Vec::with_capacity(types.len());
+    for (i, ty) in types.iter().enumerate() {
+        let head = word(data, i * 32)?;
+        let token = match *ty {
+            "address" => Token::Address(Address::from_slice(&head[12..])),
+            "bool" => Token::Bool(!U256::from(head).is_zero()),
+            "bytes" | "string" => {
+                let offset = to_usize(U256::from(head))?;
+                let len = to_usize(U256::from(word(data, offset)?))?;
+                let start = offset.checked_add(32)?;
+                let bytes = data.get(start..start.checked_add(len)?)?.to_vec();
+                if *ty == "string" {
+                    Token::String(String::from_utf8(bytes).ok()?)
+                } else {
+                    Token::Bytes(bytes)
+                }
+            }
+            _ if ty.starts_with("uint") => Token::Uint(U256::from(head)),
+            _ if ty.starts_with("int") => Token::Int(U256::from(head)),
+            _ if ty.starts_with("bytes") => {
+                let size: usize = ty[5..].parse().ok()?;
+                if size == 0 || size > 32 {
+                    return None;
+                }
+                Token::FixedBytes(head[..size].to_vec())
+            }
+            _ => return None,
+        };
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+fn word(data: &[u8], offset: usize) -> Option<&[u8]> {
+    data.get(offset..offset.checked_add(32)?)
+}
+
+fn to_usize(value: U256) -> Option<usize> {
+    if value > U256::from(u32::max_value()) {
+        return None;
+    }
+    Some(value.low_u64() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::hex;
+
+    #[test]
+    fn test_decode_error_string() {
+        let data = hex(concat!(
+            "08c379a0",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "000000000000000000000000000000000000000000000000000000000000001a",
+            "4e6f7420656e6f7567682045746865722070726f76696465642e000000000000",
+        ));
+        assert_eq!(
+            decode_revert(&data, &ErrorAbi::default()),
+            RevertReason::Error("Not enough Ether provided.".to_string())
+        );
+        // A length running past the data is not a valid string.
+        let mut truncated = data.clone();
+        truncated.truncate(4 + 64 + 16);
+        assert_eq!(
+            decode_revert(&truncated, &ErrorAbi::default()),
+            RevertReason::Unknown(truncated.clone())
+        );
+    }
+
+    #[test]
+    fn test_decode_panic() {
+        let data = hex(concat!(
+            "4e487b71",
+            "0000000000000000000000000000000000000000000000000000000000000011",
+        ));
+        assert_eq!(
+            decode_revert(&data, &ErrorAbi::default()),
+            RevertReason::Panic {
+                code: U256::from(0x11),
+                name: Some("arithmetic overflow or underflow"),
+            }
+        );
+        assert_eq!(panic_name(U256::from(0x12)), Some("division or modulo by zero"));
+        assert_eq!(panic_name(U256::from(0x32)), Some("array index out of bounds"));
+        assert_eq!(panic_name(U256::from(0x99)), None);
+    }
+
+    #[test]
+    fn test_decode_custom_error() {
+        let abi =
+            ErrorAbi::new(&["InsufficientBalance(uint256,uint256)", "Unauthorized(address)"]).unwrap();
+        let mut data = common::keccak(b"InsufficientBalance(uint256,uint256)")[..4].to_vec();
+        data.extend_from_slice(&hex(concat!(
+            "0000000000000000000000000000000000000000000000000000000000000064",
+            "00000000000000000000000000000000000000000000000000000000000000c8",
+        )));
+        assert_eq!(
+            decode_revert(&data, &abi),
+            RevertReason::Custom {
+                signature: "InsufficientBalance(uint256,uint256)".to_string(),
+                args: vec![Token::Uint(U256::from(100)), Token::Uint(U256::from(200))],
+            }
+        );
+        // Unknown selectors and empty reverts are passed through.
+        assert_eq!(
+            decode_revert(&data, &ErrorAbi::default()),
+            RevertReason::Unknown(data.clone())
+        );
+        assert_eq!(decode_revert(&[], &abi), RevertReason::Unknown(Vec::new()));
+    }
+
+    #[test]
+    fn test_error_abi_rejects_non_canonical_signatures() {
+        let mut abi = ErrorAbi::default();
+        assert_eq!(abi.add("Paused()"), Ok(()));
+        assert_eq!(abi.add("Transfer(address,uint8,bytes32,int256,bool,string,bytes)"), Ok(()));
+        for signature in &[
+            "Foo",
+            "Foo(uint256 a)",
+            "Foo(uint256, uint256)",
+            "Foo(uint256,)",
+            "Foo(uint)",
+            "Foo(uint7)",
+            "Foo(bytes33)",
+            "Foo(uint256[])",
+            "Foo((uint256,bool))",
+            "(uint256)",
+            "1Foo(uint256)",
+            "Foo(uint256))",
+        ] {
+            assert_eq!(abi.add(signature), Err(InvalidSignature(signature.to_string())));
+        }
+        assert_eq!(abi.errors.len(), 2);
+    }
+}
@@ -0,0 +1,197 @@
+use super::err::VmError;
+use ethereum_types::*;
+use sha3::{Digest, Keccak256};
+
+#[inline]
+pub fn bool_to_u256(val: bool) -> U256 {
+    if val {
+        U256::one()
+    } else {
+        U256::zero()
+    }
+}
+
+/// Returns the absolute value of a two's complement number and whether it
+/// was negative.
+#[inline]
+pub fn sign_u256(value: U256) -> (U256, bool) {
+    let U256(arr) = value;
+    let sign = arr[3].leading_zeros() == 0;
+    (set_sign(value, sign), sign)
+}
+
+/// Negates `value` in two's complement when `sign` is set.
+#[inline]
+pub fn set_sign(value: U256, sign: bool) -> U256 {
+    if sign {
+        (!U256::zero() ^ value).overflowing_add(U256::one()).0
+    } else {
+        value
+    }
+}
+
+#[inline]
+pub fn u256_min(x: U256, y: U256) -> U256 {
+    if x > y {
+        y
+    } else {
+        x
+    }
+}
+
+#[inline]
+pub fn to_u512(value: U256) -> U512 {
+    let mut buf = [0u8; 32];
+    value.to_big_endian(&mut buf);
+    U512::from_big_endian(&buf)
+}
+
+/// Takes the low 256 bits of a U512. Callers only use this on values that
+/// were reduced modulo a U256.
+#[inline]
+pub fn to_u256(value: U512) -> U256 {
+    let mut buf = [0u8; 64];
+    value.to_big_endian(&mut buf);
+    U256::from_big_endian(&buf[32..])
+}
+
+/// Converts a memory offset or size taken from the stack. Values that do not
+/// fit in 32 bits could never be paid for, so they are rejected instead of
+/// being truncated.
+#[inline]
+pub fn mem_size(value: U256) -> Result<usize, VmError> {
+    if value > U256::from(u32::max_value()) {
+        return Err(VmError::MemoryLimit);
+    }
+    Ok(value.low_u64() as usize)
+}
+
+/// Reads `size` bytes of `source` from `start`, padding with zeros past its
+/// end.
+pub fn copy_data(source: &[u8], start: U256, size: usize) -> Vec<u8> {
+    let mut data = vec![0; size];
+    if start < U256::from(source.len()) {
+        let start = start.low_u64() as usize;
+        let end = u256_min(U256::from(start) + U256::from(size), U256::from(source.len()));
+        let end = end.low_u64() as usize;
+        data[..end - start].copy_from_slice(&source[start..end]);
+    }
+    data
+}
+
+#[inline]
+pub fn keccak(data: &[u8]) -> H256 {
+    H256::from_slice(Keccak256::digest(data).as_slice())
+}
+
+#[inline]
+pub fn u256_to_h256(value: U256) -> H256 {
+    let mut buf = [0u8; 32];
+    value.to_big_endian(&mut buf);
+    H256::from_slice(&buf)
+}
+
+#[inline]
+pub fn h256_to_u256(value: H256) -> U256 {
+    U256::from_big_endian(&value[..])
+}
+
+/// Takes the low 160 bits of a stack item as an address.
+#[inline]
+pub fn u256_to_address(value: U256) -> Address {
+    Address::from_slice(&u256_to_h256(value)[12..])
+}
+
+#[inline]
+pub fn address_to_u256(value: Address) -> U256 {
+    U256::from_big_endian(&value[..])
+}
+
+/// Address of a contract created with CREATE: the last 20 bytes of
+/// keccak(rlp([sender, nonce])).
+pub fn create_address(sender: Address, nonce: U256) -> Address {
+    let mut nonce_bytes = [0u8; 32];
+    nonce.to_big_endian(&mut nonce_bytes);
+    let nonce_bytes = &nonce_bytes[32 - (nonce.bits() + 7) / 8..];
+    // Both items are short, so the list fits in a single-byte header.
+    let mut rlp = Vec::with_capacity(56);
+    rlp.push(0);
+    rlp.push(0x80 + 20);
+    rlp.extend_from_slice(&sender[..]);
+    if nonce_bytes.len() == 1 && nonce_bytes[0] < 0x80 {
+        rlp.push(nonce_bytes[0]);
+    } else {
+        rlp.push(0x80 + nonce_bytes.len() as u8);
+        rlp.extend_from_slice(nonce_bytes);
+    }
+    rlp[0] = 0xc0 + (rlp.len() - 1) as u8;
+    Address::from_slice(&keccak(&rlp)[12..])
+}
+
+/// Address of a contract created with CREATE2 (EIP-1014): the last 20 bytes
+/// of keccak(0xff ++ sender ++ salt ++ keccak(init_code)).
+pub fn create2_address(sender: Address, salt: H256, code_hash: H256) -> Address {
+    let mut buf = Vec::with_capacity(85);
+    buf.push(0xff);
+    buf.extend_from_slice(&sender[..]);
+    buf.extend_from_slice(&salt[..]);
+    buf.extend_from_slice(&code_hash[..]);
+    Address::from_slice(&keccak(&buf)[12..])
+}
+
+/// Decodes a hex string, for writing test vectors.
+#[cfg(test)]
+pub fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_create_address() {
+        let sender = Address::from_str("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0").unwrap();
+        let expected = [
+            "cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d",
+            "343c43a37d37dff08ae8c4a11544c718abb4fcf8",
+            "f778b86fa74e846c4f0a1fbd1335fe81c00a0c91",
+        ];
+        for (nonce, address) in expected.iter().enumerate() {
+            assert_eq!(
+                create_address(sender, U256::from(nonce)),
+                Address::from_str(address).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_create2_address() {
+        // Examples from EIP-1014.
+        let code_hash = keccak(&[0x00]);
+        assert_eq!(
+            create2_address(Address::zero(), H256::zero(), code_hash),
+            Address::from_str("4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38").unwrap()
+        );
+        let sender = Address::from_str("deadbeef00000000000000000000000000000000").unwrap();
+        let salt = H256::from_str("000000000000000000000000feed000000000000000000000000000000000000").unwrap();
+        assert_eq!(
+            create2_address(sender, salt, code_hash),
+            Address::from_str("d04116cdd17bebe565eb2422f2497e06cc1c9833").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_copy_data() {
+        let source = [1u8, 2, 3, 4];
+        assert_eq!(copy_data(&source, U256::one(), 2), vec![2, 3]);
+        assert_eq!(copy_data(&source, U256::from(2), 4), vec![3, 4, 0, 0]);
+        assert_eq!(copy_data(&source, U256::from(4), 2), vec![0, 0]);
+        assert_eq!(copy_data(&source, !U256::zero(), 3), vec![0, 0, 0]);
+        assert_eq!(copy_data(&source, U256::zero(), 0), Vec::<u8>::new());
+    }
+}
@@ -1,18 +1,82 @@
+use super::common;
+use super::err::VmError;
+use super::host::Log;
 use super::memory;
 use super::opcodes;
+use super::precompiled::Precompiles;
+use super::spec::Fork;
 use super::stack;
 use ethereum_types::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+/// Bitmap of the positions in a piece of code that are valid JUMPDESTs, that
+/// is JUMPDEST bytes which are not part of a PUSH immediate.
+pub struct JumpDests {
+    bits: Vec<u64>,
+}
+
+impl JumpDests {
+    pub fn analyze(code: &[u8]) -> Self {
+        let mut bits = vec![0u64; (code.len() + 63) / 64];
+        let mut pc = 0;
+        while pc < code.len() {
+            let op = code[pc];
+            if op == opcodes::OpCode::JUMPDEST as u8 {
+                bits[pc / 64] |= 1 << (pc % 64);
+            } else if let Some(info) = opcodes::op_info(op) {
+                pc += info.immediate;
+            }
+            pc += 1;
+        }
+        JumpDests { bits }
+    }
+
+    pub fn contains(&self, pc: u64) -> bool {
+        match self.bits.get((pc / 64) as usize) {
+            Some(word) => word & (1 << (pc % 64)) != 0,
+            None => false,
+        }
+    }
+}
+
+/// JUMPDEST analysis shared between contracts, keyed by code hash, so that
+/// code is analyzed at most once no matter how often it is called.
+pub type JumpDestCache = Rc<RefCell<HashMap<H256, Rc<JumpDests>>>>;
 
 pub struct Contract {
+    /// Address whose storage and balance the code runs against.
+    pub address: Address,
+    pub caller: Address,
+    pub value: U256,
+    pub input: Vec<u8>,
     pub code: Vec<u8>,
+    /// Hash of `code`, computed on first use when left as zero.
+    pub code_hash: H256,
     pub cgas: u64,
+    pub jumpdests: JumpDestCache,
+    analysis: Option<Rc<JumpDests>>,
 }
 
 impl Contract {
     pub fn new() -> Self {
+        Contract::with_jumpdests(Rc::new(RefCell::new(HashMap::new())))
+    }
+
+    /// A contract whose JUMPDEST analysis goes through `jumpdests`.
+    pub fn with_jumpdests(jumpdests: JumpDestCache) -> Self {
         Contract {
+            address: Address::zero(),
+            caller: Address::zero(),
+            value: U256::zero(),
+            input: Vec::new(),
             code: vec![0; 0],
+            code_hash: H256::zero(),
             cgas: 21000,
+            jumpdests,
+            analysis: None,
         }
     }
 
@@ -23,8 +87,31 @@ impl Contract {
         0
     }
 
-    pub fn get_opcode(&self, n: u64) -> opcodes::OpCode {
-        opcodes::OpCode::from(self.get_byte(n))
+    pub fn get_opcode(&self, n: u64) -> Result<opcodes::OpCode, VmError> {
+        opcodes::OpCode::try_from(self.get_byte(n))
+    }
+
+    /// Whether `dest` is a JUMPDEST in code rather than push data.
+    pub fn valid_jumpdest(&mut self, dest: U256) -> bool {
+        if dest >= U256::from(self.code.len()) {
+            return false;
+        }
+        if self.analysis.is_none() {
+            if self.code_hash.is_zero() {
+                self.code_hash = common::keccak(&self.code);
+            }
+            let analysis = self
+                .jumpdests
+                .borrow_mut()
+                .entry(self.code_hash)
+                .or_insert_with(|| Rc::new(JumpDests::analyze(&self.code)))
+                .clone();
+            self.analysis = Some(analysis);
+        }
+        match self.analysis {
+            Some(ref analysis) => analysis.contains(dest.low_u64()),
+            None => false,
+        }
     }
 
     pub fn use_gas(&mut self, gas: u64) -> bool {
@@ -36,22 +123,163 @@ impl Contract {
     }
 }
 
-pub struct EVMInfo {}
+/// Accounts and storage slots accessed so far in the transaction, which are
+/// cheaper to access again from Berlin on (EIP-2929).
+#[derive(Clone, Default)]
+pub struct AccessSet {
+    pub addresses: HashSet<Address>,
+    pub slots: HashSet<(Address, H256)>,
+}
 
-impl EVMInfo {
-    pub fn new() -> Self {
-        EVMInfo {}
+impl AccessSet {
+    /// Marks `address` as accessed and returns whether it was cold.
+    pub fn touch_address(&mut self, address: Address) -> bool {
+        self.addresses.insert(address)
+    }
+
+    /// Marks a storage slot as accessed and returns whether it was cold.
+    pub fn touch_slot(&mut self, address: Address, key: H256) -> bool {
+        self.slots.insert((address, key))
     }
 }
 
+/// Execution rules and gas schedule. Every gas constant used by the
+/// interpreter comes from here, so that chains pinned to different forks can
+/// share one interpreter.
+#[derive(Clone)]
 pub struct EVMConf {
+    pub fork: Fork,
     pub tier_step_gas: [u64; 8],
+    pub gas_exp: u64,
+    pub gas_exp_byte: u64,
+    pub gas_sha3: u64,
+    pub gas_sha3_word: u64,
+    pub gas_copy: u64,
+    pub gas_log: u64,
+    pub gas_log_topic: u64,
+    pub gas_log_data: u64,
+    pub gas_memory: u64,
+    pub gas_quad_coeff_div: u64,
+    /// Hard cap on the memory of a single frame, in bytes. Expanding past it
+    /// halts with `MemoryLimit` however much gas is left.
+    pub max_memory: usize,
+    pub gas_jumpdest: u64,
+    pub gas_balance: u64,
+    /// Base cost of EXTCODESIZE and EXTCODECOPY.
+    pub gas_ext_code: u64,
+    pub gas_ext_code_hash: u64,
+    pub gas_sload: u64,
+    pub gas_sstore_set: u64,
+    pub gas_sstore_reset: u64,
+    pub gas_sstore_clear_refund: u64,
+    /// EIP-2200 net gas metering for SSTORE.
+    pub sstore_net_metering: bool,
+    /// SSTORE fails when no more than this much gas is left (EIP-2200).
+    pub gas_sstore_sentry: u64,
+    /// Charge the first access to an account or storage slot in a
+    /// transaction at the cold costs below (EIP-2929). The warm costs are
+    /// `gas_balance`, `gas_sload` and friends.
+    pub access_lists: bool,
+    pub gas_cold_account_access: u64,
+    pub gas_cold_sload: u64,
+    pub gas_call: u64,
+    pub gas_call_value: u64,
+    pub gas_call_stipend: u64,
+    pub gas_new_account: u64,
+    /// Forward at most all but one 64th of the remaining gas to calls and
+    /// creates (EIP-150).
+    pub call_gas_cap: bool,
+    /// Only charge for new accounts when value is sent to an empty account
+    /// (EIP-161).
+    pub empty_account_is_new: bool,
+    pub call_depth_limit: usize,
+    pub gas_create: u64,
+    pub gas_code_deposit: u64,
+    /// Per-word cost of init code (EIP-3860).
+    pub gas_initcode_word: u64,
+    pub max_code_size: usize,
+    pub max_initcode_size: usize,
+    pub gas_selfdestruct: u64,
+    pub gas_selfdestruct_refund: u64,
+    /// SELFDESTRUCT only deletes contracts created in the same transaction
+    /// (EIP-6780).
+    pub selfdestruct_only_new: bool,
+    /// At most 1/max_refund_quotient of the gas used is refunded.
+    pub max_refund_quotient: u64,
+    pub precompiles: Precompiles,
 }
 
 impl EVMConf {
     pub fn new() -> Self {
+        EVMConf::with_fork(Fork::Cancun)
+    }
+
+    pub fn with_fork(fork: Fork) -> Self {
+        let berlin = fork >= Fork::Berlin;
+        let london = fork >= Fork::London;
+        let istanbul = fork >= Fork::Istanbul;
+        let eip150 = fork >= Fork::TangerineWhistle;
+        let eip160 = fork >= Fork::SpuriousDragon;
         EVMConf {
+            fork,
             tier_step_gas: [0, 2, 3, 5, 8, 10, 20, 0],
+            gas_exp: 10,
+            gas_exp_byte: if eip160 { 50 } else { 10 },
+            gas_sha3: 30,
+            gas_sha3_word: 6,
+            gas_copy: 3,
+            gas_log: 375,
+            gas_log_topic: 375,
+            gas_log_data: 8,
+            gas_memory: 3,
+            gas_quad_coeff_div: 512,
+            max_memory: memory::DEFAULT_MEMORY_LIMIT,
+            gas_jumpdest: 1,
+            gas_balance: if berlin {
+                100
+            } else if istanbul {
+                700
+            } else if eip150 {
+                400
+            } else {
+                20
+            },
+            gas_ext_code: if berlin { 100 } else if eip150 { 700 } else { 20 },
+            gas_ext_code_hash: if berlin { 100 } else if istanbul { 700 } else { 400 },
+            gas_sload: if berlin {
+                100
+            } else if istanbul {
+                800
+            } else if eip150 {
+                200
+            } else {
+                50
+            },
+            gas_sstore_set: 20000,
+            gas_sstore_reset: if berlin { 2900 } else { 5000 },
+            gas_sstore_clear_refund: if london { 4800 } else { 15000 },
+            sstore_net_metering: istanbul,
+            gas_sstore_sentry: 2300,
+            access_lists: berlin,
+            gas_cold_account_access: 2600,
+            gas_cold_sload: 2100,
+            gas_call: if berlin { 100 } else if eip150 { 700 } else { 40 },
+            gas_call_value: 9000,
+            gas_call_stipend: 2300,
+            gas_new_account: 25000,
+            call_gas_cap: eip150,
+            empty_account_is_new: fork >= Fork::SpuriousDragon,
+            call_depth_limit: 1024,
+            gas_create: 32000,
+            gas_code_deposit: 200,
+            gas_initcode_word: if fork >= Fork::Shanghai { 2 } else { 0 },
+            max_code_size: if fork >= Fork::SpuriousDragon { 0x6000 } else { usize::max_value() },
+            max_initcode_size: if fork >= Fork::Shanghai { 0xc000 } else { usize::max_value() },
+            gas_selfdestruct: if eip150 { 5000 } else { 0 },
+            gas_selfdestruct_refund: if london { 0 } else { 24000 },
+            selfdestruct_only_new: fork >= Fork::Cancun,
+            max_refund_quotient: if london { 5 } else { 2 },
+            precompiles: Precompiles::with_fork(fork),
         }
     }
 }
@@ -59,10 +287,20 @@ impl EVMConf {
 pub struct EVMContext {
     pub stack: stack::Stack<U256>,
     pub memory: memory::Memory,
-    pub info: EVMInfo,
-    pub conf: EVMConf,
+    /// Shared by every frame of the transaction.
+    pub conf: Rc<EVMConf>,
     pub contract: Contract,
     pub return_data: Vec<u8>,
+    pub access: AccessSet,
+    /// Number of frames above this one; 0 for the transaction's frame.
+    pub depth: usize,
+    /// Set under STATICCALL (EIP-214), where nothing may modify state.
+    pub is_static: bool,
+    /// Logs emitted by this frame and the calls it made that succeeded.
+    pub logs: Vec<Log>,
+    /// Contracts created so far in the transaction, which SELFDESTRUCT may
+    /// still delete under EIP-6780.
+    pub created: HashSet<Address>,
 }
 
 impl EVMContext {
@@ -70,10 +308,89 @@ impl EVMContext {
         EVMContext {
             stack: stack::Stack::with_capacity(1024),
             memory: memory::Memory::new(),
-            info: EVMInfo::new(),
-            conf: EVMConf::new(),
+            conf: Rc::new(EVMConf::new()),
             contract: Contract::new(),
             return_data: Vec::new(),
+            access: AccessSet::default(),
+            depth: 0,
+            is_static: false,
+            logs: Vec::new(),
+            created: HashSet::new(),
+        }
+    }
+
+    /// Context for a frame entered from this one. It shares the
+    /// configuration and the JUMPDEST cache, and starts from the accounts
+    /// accessed and the contracts created so far.
+    pub fn child(&self) -> Self {
+        EVMContext {
+            stack: stack::Stack::with_capacity(1024),
+            memory: memory::Memory::new(),
+            conf: self.conf.clone(),
+            contract: Contract::with_jumpdests(self.contract.jumpdests.clone()),
+            return_data: Vec::new(),
+            access: self.access.clone(),
+            depth: self.depth + 1,
+            is_static: self.is_static,
+            logs: Vec::new(),
+            created: self.created.clone(),
         }
     }
+
+    /// Marks `address` as accessed and returns whether it was cold.
+    /// Precompiles are always warm (EIP-2929).
+    pub fn touch_address(&mut self, address: Address) -> bool {
+        !self.conf.precompiles.contains(&address) && self.access.touch_address(address)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_jumpdests_skip_push_data() {
+        let code = vec![
+            opcodes::OpCode::JUMPDEST as u8,
+            opcodes::OpCode::PUSH2 as u8,
+            opcodes::OpCode::JUMPDEST as u8,
+            opcodes::OpCode::JUMPDEST as u8,
+            opcodes::OpCode::JUMPDEST as u8,
+        ];
+        let analysis = JumpDests::analyze(&code);
+        assert!(analysis.contains(0));
+        assert!(!analysis.contains(1));
+        assert!(!analysis.contains(2));
+        assert!(!analysis.contains(3));
+        assert!(analysis.contains(4));
+        assert!(!analysis.contains(1024));
+    }
+
+    #[test]
+    fn test_jumpdests_cached_by_code_hash() {
+        let code = vec![opcodes::OpCode::JUMPDEST as u8];
+        let mut a = Contract::new();
+        a.code = code.clone();
+        let mut b = Contract::new();
+        b.code = code;
+        b.jumpdests = a.jumpdests.clone();
+        assert!(a.valid_jumpdest(U256::zero()));
+        assert!(b.valid_jumpdest(U256::zero()));
+        assert!(!b.valid_jumpdest(U256::one()));
+        assert_eq!(a.jumpdests.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_child_context_shares_conf_and_cache() {
+        let mut parent = EVMContext::new();
+        parent.is_static = true;
+        parent.access.touch_address(Address::from_low_u64_be(0xaa));
+        let child = parent.child();
+        assert!(Rc::ptr_eq(&child.conf, &parent.conf));
+        assert!(Rc::ptr_eq(&child.contract.jumpdests, &parent.contract.jumpdests));
+        assert_eq!(child.depth, 1);
+        assert!(child.is_static);
+        assert!(child.access.addresses.contains(&Address::from_low_u64_be(0xaa)));
+    }
 }
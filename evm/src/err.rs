@@ -0,0 +1,49 @@
+use std::error;
+use std::fmt;
+
+/// Exceptional halting conditions of the interpreter. Any of these consumes
+/// all gas given to the current frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VmError {
+    StackUnderflow,
+    StackOverflow,
+    OutOfGas,
+    InvalidJump,
+    InvalidOpcode(u8),
+    /// Memory would grow past `EVMConf::max_memory`.
+    MemoryLimit,
+    /// Memory was accessed outside the part already expanded. Instructions
+    /// always expand memory first, so this points at a bug in the
+    /// interpreter rather than at the code being run.
+    MemoryOutOfRange,
+    StaticViolation,
+    /// Init code or deployed code is larger than the fork allows.
+    CodeSizeLimit,
+    /// The host holds a nonce it cannot read.
+    InvalidNonce,
+    /// A precompiled contract rejected its input.
+    PrecompileFailure,
+    /// RETURNDATACOPY read past the end of the return data (EIP-211).
+    ReturnDataOutOfBounds,
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VmError::StackUnderflow => write!(f, "stack underflow"),
+            VmError::StackOverflow => write!(f, "stack overflow"),
+            VmError::OutOfGas => write!(f, "out of gas"),
+            VmError::InvalidJump => write!(f, "invalid jump destination"),
+            VmError::InvalidOpcode(op) => write!(f, "invalid opcode: {:#04x}", op),
+            VmError::MemoryLimit => write!(f, "memory limit exceeded"),
+            VmError::MemoryOutOfRange => write!(f, "memory access out of range"),
+            VmError::StaticViolation => write!(f, "state modification in static call"),
+            VmError::CodeSizeLimit => write!(f, "code size limit exceeded"),
+            VmError::InvalidNonce => write!(f, "invalid account nonce"),
+            VmError::PrecompileFailure => write!(f, "precompiled contract failed"),
+            VmError::ReturnDataOutOfBounds => write!(f, "return data out of bounds"),
+        }
+    }
+}
+
+impl error::Error for VmError {}
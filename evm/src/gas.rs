@@ -0,0 +1,226 @@
+//! Gas functions for instructions in the `GasPriceTier::Special` tier and for
+//! memory expansion. Every function is evaluated against the stack arguments
+//! before the instruction pops them, so it can be charged up front.
+use super::common;
+use super::core::EVMConf;
+use super::err::VmError;
+use super::opcodes::OpCode;
+use super::stack::Stack;
+use ethereum_types::*;
+use std::cmp;
+
+/// Number of 32-byte words needed to hold `size` bytes.
+#[inline]
+fn to_word_size(size: u64) -> u64 {
+    (size + 31) / 32
+}
+
+/// Total cost of a memory of `words` words.
+#[inline]
+fn memory_cost(conf: &EVMConf, words: u64) -> u64 {
+    conf.gas_memory * words + words * words / conf.gas_quad_coeff_div
+}
+
+/// Cost of expanding memory from `mem_len` bytes so that `offset..offset+size`
+/// is addressable. Touching zero bytes never expands memory, and expanding
+/// past `conf.max_memory` fails before anything is allocated.
+pub fn memory_gas(
+    conf: &EVMConf,
+    mem_len: usize,
+    offset: U256,
+    size: U256,
+) -> Result<u64, VmError> {
+    if size.is_zero() {
+        return Ok(0);
+    }
+    let end = common::mem_size(offset)? as u64 + common::mem_size(size)? as u64;
+    if end > conf.max_memory as u64 {
+        return Err(VmError::MemoryLimit);
+    }
+    let new_words = to_word_size(end);
+    let old_words = to_word_size(mem_len as u64);
+    if new_words <= old_words {
+        return Ok(0);
+    }
+    Ok(memory_cost(conf, new_words) - memory_cost(conf, old_words))
+}
+
+/// EXP costs a fixed amount plus a charge per byte of the exponent.
+pub fn exp_gas(conf: &EVMConf, exponent: U256) -> u64 {
+    let bytes = (exponent.bits() as u64 + 7) / 8;
+    conf.gas_exp + conf.gas_exp_byte * bytes
+}
+
+/// SHA3 costs a fixed amount plus a charge per hashed word.
+pub fn sha3_gas(conf: &EVMConf, mem_len: usize, offset: U256, size: U256) -> Result<u64, VmError> {
+    let mem_gas = memory_gas(conf, mem_len, offset, size)?;
+    let words = to_word_size(common::mem_size(size)? as u64);
+    Ok(conf.gas_sha3 + conf.gas_sha3_word * words + mem_gas)
+}
+
+/// CALLDATACOPY, CODECOPY, EXTCODECOPY and RETURNDATACOPY charge per copied
+/// word on top of their base cost.
+pub fn copy_gas(
+    conf: &EVMConf,
+    mem_len: usize,
+    mem_offset: U256,
+    size: U256,
+) -> Result<u64, VmError> {
+    let mem_gas = memory_gas(conf, mem_len, mem_offset, size)?;
+    let words = to_word_size(common::mem_size(size)? as u64);
+    Ok(conf.gas_copy * words + mem_gas)
+}
+
+/// LOG0 to LOG4 charge per topic and per byte of data.
+pub fn log_gas(
+    conf: &EVMConf,
+    mem_len: usize,
+    topics: usize,
+    offset: U256,
+    size: U256,
+) -> Result<u64, VmError> {
+    let mem_gas = memory_gas(conf, mem_len, offset, size)?;
+    let data = common::mem_size(size)? as u64;
+    Ok(conf.gas_log + conf.gas_log_topic * topics as u64 + conf.gas_log_data * data + mem_gas)
+}
+
+/// The call opcodes read their input from and write their output to memory,
+/// so memory is expanded to cover whichever window ends last.
+pub fn call_memory_gas(
+    conf: &EVMConf,
+    mem_len: usize,
+    in_offset: U256,
+    in_size: U256,
+    out_offset: U256,
+    out_size: U256,
+) -> Result<u64, VmError> {
+    let in_gas = memory_gas(conf, mem_len, in_offset, in_size)?;
+    let out_gas = memory_gas(conf, mem_len, out_offset, out_size)?;
+    Ok(cmp::max(in_gas, out_gas))
+}
+
+/// CREATE and CREATE2 charge per word of init code (EIP-3860), and CREATE2
+/// also pays for hashing it.
+pub fn create_gas(
+    conf: &EVMConf,
+    mem_len: usize,
+    offset: U256,
+    size: U256,
+    hashed: bool,
+) -> Result<u64, VmError> {
+    let mem_gas = memory_gas(conf, mem_len, offset, size)?;
+    let size = common::mem_size(size)?;
+    if size > conf.max_initcode_size {
+        return Err(VmError::CodeSizeLimit);
+    }
+    let words = to_word_size(size as u64);
+    let mut gas = conf.gas_create + conf.gas_initcode_word * words + mem_gas;
+    if hashed {
+        gas += conf.gas_sha3_word * words;
+    }
+    Ok(gas)
+}
+
+/// Gas charged for `op` in addition to its tier cost.
+pub fn dynamic_gas(
+    conf: &EVMConf,
+    op: &OpCode,
+    stack: &Stack<U256>,
+    mem_len: usize,
+) -> Result<u64, VmError> {
+    match op {
+        OpCode::EXP => Ok(exp_gas(conf, stack.back(1)?)),
+        OpCode::SHA3 => sha3_gas(conf, mem_len, stack.back(0)?, stack.back(1)?),
+        OpCode::CALLDATACOPY | OpCode::CODECOPY | OpCode::RETURNDATACOPY => {
+            copy_gas(conf, mem_len, stack.back(0)?, stack.back(2)?)
+        }
+        OpCode::EXTCODECOPY => {
+            Ok(conf.gas_ext_code + copy_gas(conf, mem_len, stack.back(1)?, stack.back(3)?)?)
+        }
+        OpCode::LOG0 | OpCode::LOG1 | OpCode::LOG2 | OpCode::LOG3 | OpCode::LOG4 => {
+            let topics = (*op as u8 - OpCode::LOG0 as u8) as usize;
+            log_gas(conf, mem_len, topics, stack.back(0)?, stack.back(1)?)
+        }
+        OpCode::MLOAD | OpCode::MSTORE => memory_gas(conf, mem_len, stack.back(0)?, U256::from(32)),
+        OpCode::MSTORE8 => memory_gas(conf, mem_len, stack.back(0)?, U256::one()),
+        OpCode::RETURN | OpCode::REVERT => {
+            memory_gas(conf, mem_len, stack.back(0)?, stack.back(1)?)
+        }
+        OpCode::CALL | OpCode::CALLCODE => call_memory_gas(
+            conf,
+            mem_len,
+            stack.back(3)?,
+            stack.back(4)?,
+            stack.back(5)?,
+            stack.back(6)?,
+        ),
+        OpCode::DELEGATECALL | OpCode::STATICCALL => call_memory_gas(
+            conf,
+            mem_len,
+            stack.back(2)?,
+            stack.back(3)?,
+            stack.back(4)?,
+            stack.back(5)?,
+        ),
+        OpCode::CREATE => create_gas(conf, mem_len, stack.back(1)?, stack.back(2)?, false),
+        OpCode::CREATE2 => create_gas(conf, mem_len, stack.back(1)?, stack.back(2)?, true),
+        OpCode::JUMPDEST => Ok(conf.gas_jumpdest),
+        OpCode::BALANCE => Ok(conf.gas_balance),
+        OpCode::EXTCODESIZE => Ok(conf.gas_ext_code),
+        OpCode::EXTCODEHASH => Ok(conf.gas_ext_code_hash),
+        _ => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::Fork;
+
+    #[test]
+    fn test_memory_gas() {
+        let conf = EVMConf::new();
+        assert_eq!(memory_gas(&conf, 0, U256::from(1000), U256::zero()), Ok(0));
+        assert_eq!(memory_gas(&conf, 0, U256::zero(), U256::from(32)), Ok(3));
+        assert_eq!(memory_gas(&conf, 32, U256::zero(), U256::from(32)), Ok(0));
+        // 1024 words cost 3 * 1024 + 1024 * 1024 / 512.
+        assert_eq!(
+            memory_gas(&conf, 0, U256::zero(), U256::from(32 * 1024)),
+            Ok(5120)
+        );
+        assert_eq!(
+            memory_gas(&conf, 32, U256::zero(), U256::from(32 * 1024)),
+            Ok(5117)
+        );
+
+        let mut conf = EVMConf::new();
+        conf.max_memory = 1024;
+        assert_eq!(memory_gas(&conf, 0, U256::from(992), U256::from(32)), Ok(98));
+        assert_eq!(
+            memory_gas(&conf, 0, U256::from(993), U256::from(32)),
+            Err(VmError::MemoryLimit)
+        );
+    }
+
+    #[test]
+    fn test_exp_gas() {
+        let conf = EVMConf::new();
+        assert_eq!(exp_gas(&conf, U256::zero()), 10);
+        assert_eq!(exp_gas(&conf, U256::from(0xff)), 60);
+        assert_eq!(exp_gas(&conf, U256::from(0x100)), 110);
+
+        let conf = EVMConf::with_fork(Fork::Homestead);
+        assert_eq!(exp_gas(&conf, U256::from(0x100)), 30);
+    }
+
+    #[test]
+    fn test_copy_and_log_gas() {
+        let conf = EVMConf::new();
+        assert_eq!(sha3_gas(&conf, 64, U256::zero(), U256::from(33)), Ok(42));
+        assert_eq!(copy_gas(&conf, 0, U256::zero(), U256::from(64)), Ok(12));
+        assert_eq!(
+            log_gas(&conf, 32, 2, U256::zero(), U256::from(10)),
+            Ok(1205)
+        );
+    }
+}
@@ -0,0 +1,483 @@
+use super::common;
+use super::err::VmError;
+use ethereum_types::*;
+use std::collections::HashMap;
+use std::mem;
+
+/// An event emitted by LOG0 to LOG4.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Log {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Vec<u8>,
+}
+
+/// The block a transaction executes in, read by COINBASE to BASEFEE.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockEnv {
+    pub coinbase: Address,
+    pub timestamp: U256,
+    pub number: U256,
+    /// The block difficulty, or PREVRANDAO after the merge (EIP-4399). Both
+    /// are read by opcode 0x44.
+    pub difficulty: U256,
+    pub gas_limit: U256,
+    pub chain_id: U256,
+    /// Zero before London (EIP-1559).
+    pub base_fee: U256,
+}
+
+/// The transaction being executed, read by ORIGIN and GASPRICE.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TxEnv {
+    /// Hash of the transaction, recorded on the logs it emits.
+    pub hash: H256,
+    pub origin: Address,
+    pub gas_price: U256,
+}
+
+/// Looks up the hashes of past blocks for BLOCKHASH. The interpreter only
+/// asks for the 256 most recent blocks.
+pub trait BlockHashProvider {
+    /// Hash of block `number`, or zero if it is unknown.
+    fn block_hash(&self, number: U256) -> H256;
+}
+
+impl<F: Fn(U256) -> H256> BlockHashProvider for F {
+    fn block_hash(&self, number: U256) -> H256 {
+        self(number)
+    }
+}
+
+/// A `BlockHashProvider` backed by a map, for tests and tooling.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryBlockHashes {
+    hashes: HashMap<U256, H256>,
+}
+
+impl InMemoryBlockHashes {
+    pub fn new() -> Self {
+        InMemoryBlockHashes::default()
+    }
+
+    pub fn insert(&mut self, number: U256, hash: H256) {
+        self.hashes.insert(number, hash);
+    }
+}
+
+impl BlockHashProvider for InMemoryBlockHashes {
+    fn block_hash(&self, number: U256) -> H256 {
+        self.hashes.get(&number).cloned().unwrap_or_default()
+    }
+}
+
+/// Everything the interpreter needs to know about the world outside the
+/// running code: accounts, storage, the block and the transaction.
+pub trait Host {
+    fn get_balance(&self, address: Address) -> U256;
+    fn get_code(&self, address: Address) -> Vec<u8>;
+    fn get_code_hash(&self, address: Address) -> H256;
+    fn get_code_size(&self, address: Address) -> usize;
+    fn set_code(&mut self, address: Address, code: &[u8]);
+    /// Fails if the stored nonce is corrupt, rather than reading it as zero
+    /// and deriving a colliding CREATE address.
+    fn get_nonce(&self, address: Address) -> Result<U256, VmError>;
+    fn set_nonce(&mut self, address: Address, nonce: U256);
+    /// Creates a fresh account at `address`, keeping any balance it already
+    /// holds.
+    fn create_account(&mut self, address: Address);
+    /// Marks the account for deletion at the end of the transaction and
+    /// zeroes its balance. Returns false if the account does not exist.
+    fn suicide(&mut self, address: Address) -> bool;
+    fn has_suicided(&self, address: Address) -> bool;
+    fn add_balance(&mut self, address: Address, value: U256);
+    /// Callers make sure the account holds at least `value`.
+    fn sub_balance(&mut self, address: Address, value: U256);
+    fn exists(&self, address: Address) -> bool;
+    /// Whether the account has no code, a zero nonce and a zero balance
+    /// (EIP-161).
+    fn empty(&self, address: Address) -> bool;
+
+    fn get_state(&self, address: Address, key: H256) -> H256;
+    fn set_state(&mut self, address: Address, key: H256, value: H256);
+    /// Value of a storage slot at the start of the transaction.
+    fn get_committed_state(&self, address: Address, key: H256) -> H256;
+
+    fn add_refund(&mut self, gas: u64);
+    fn sub_refund(&mut self, gas: u64);
+    /// The refund counter. It may dip below zero while a transaction runs,
+    /// when a refund granted in a frame that was since rolled back is taken
+    /// back again.
+    fn get_refund(&self) -> i64;
+
+    fn get_block_hash(&self, number: U256) -> H256;
+    fn add_log(&mut self, log: Log);
+
+    /// Marks the current state so it can be restored by
+    /// `revert_to_snapshot`, undoing every change made after it.
+    fn snapshot(&mut self) -> usize;
+    fn revert_to_snapshot(&mut self, id: usize);
+    /// Keeps every change made since the snapshot and forgets it, along
+    /// with any snapshot taken after it.
+    fn discard_snapshot(&mut self, id: usize);
+
+    fn block(&self) -> &BlockEnv;
+    fn tx(&self) -> &TxEnv;
+}
+
+#[derive(Clone, Default)]
+pub struct Account {
+    pub balance: U256,
+    pub nonce: U256,
+    pub code: Vec<u8>,
+    pub storage: HashMap<H256, H256>,
+    /// Deleted by SELFDESTRUCT when the transaction ends.
+    pub suicided: bool,
+}
+
+/// A `Host` keeping all state in memory, for tests and tooling.
+#[derive(Clone, Default)]
+pub struct InMemoryHost {
+    /// Changes made here directly rather than through `Host` are not undone
+    /// by `revert_to_snapshot`.
+    pub accounts: HashMap<Address, Account>,
+    /// Storage as of the start of the transaction.
+    pub committed: HashMap<Address, HashMap<H256, H256>>,
+    pub block_hashes: InMemoryBlockHashes,
+    pub logs: Vec<Log>,
+    pub refund: i64,
+    pub block: BlockEnv,
+    pub tx: TxEnv,
+    pub(crate) snapshots: Vec<Snapshot>,
+    /// Changes made while a snapshot is held, oldest first.
+    journal: Vec<JournalEntry>,
+}
+
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    journal: usize,
+    logs: usize,
+    refund: i64,
+}
+
+/// A change to an account, holding the value it replaced.
+#[derive(Clone)]
+enum JournalEntry {
+    /// The account did not exist.
+    Created(Address),
+    /// The account was reset by `create_account`.
+    Replaced(Address, Account),
+    Balance(Address, U256),
+    Nonce(Address, U256),
+    Code(Address, Vec<u8>),
+    Suicided(Address, bool),
+    Storage(Address, H256, Option<H256>),
+}
+
+impl InMemoryHost {
+    pub fn new() -> Self {
+        InMemoryHost::default()
+    }
+
+    /// Ends the transaction: deletes self-destructed accounts, makes the
+    /// current storage the committed storage and drops all snapshots.
+    pub fn commit(&mut self) {
+        self.accounts.retain(|_, account| !account.suicided);
+        self.committed = self
+            .accounts
+            .iter()
+            .map(|(address, account)| (*address, account.storage.clone()))
+            .collect();
+        self.refund = 0;
+        self.snapshots.clear();
+        self.journal.clear();
+    }
+
+    /// Records a change so that reverting to an earlier snapshot undoes it.
+    /// Nothing is kept while no snapshot is held.
+    fn record(&mut self, entry: JournalEntry) {
+        if !self.snapshots.is_empty() {
+            self.journal.push(entry);
+        }
+    }
+
+    /// The account at `address`, created empty if it does not exist.
+    fn account_mut(&mut self, address: Address) -> &mut Account {
+        if !self.accounts.contains_key(&address) {
+            self.record(JournalEntry::Created(address));
+        }
+        self.accounts.entry(address).or_default()
+    }
+
+    fn undo(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::Created(address) => {
+                self.accounts.remove(&address);
+            }
+            JournalEntry::Replaced(address, account) => {
+                self.accounts.insert(address, account);
+            }
+            JournalEntry::Balance(address, balance) => {
+                if let Some(account) = self.accounts.get_mut(&address) {
+                    account.balance = balance;
+                }
+            }
+            JournalEntry::Nonce(address, nonce) => {
+                if let Some(account) = self.accounts.get_mut(&address) {
+                    account.nonce = nonce;
+                }
+            }
+            JournalEntry::Code(address, code) => {
+                if let Some(account) = self.accounts.get_mut(&address) {
+                    account.code = code;
+                }
+            }
+            JournalEntry::Suicided(address, suicided) => {
+                if let Some(account) = self.accounts.get_mut(&address) {
+                    account.suicided = suicided;
+                }
+            }
+            JournalEntry::Storage(address, key, value) => {
+                if let Some(account) = self.accounts.get_mut(&address) {
+                    match value {
+                        Some(value) => account.storage.insert(key, value),
+                        None => account.storage.remove(&key),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Host for InMemoryHost {
+    fn get_balance(&self, address: Address) -> U256 {
+        self.accounts.get(&address).map(|a| a.balance).unwrap_or_default()
+    }
+
+    fn get_code(&self, address: Address) -> Vec<u8> {
+        self.accounts.get(&address).map(|a| a.code.clone()).unwrap_or_default()
+    }
+
+    fn get_code_hash(&self, address: Address) -> H256 {
+        match self.accounts.get(&address) {
+            Some(a) => common::keccak(&a.code),
+            None => H256::zero(),
+        }
+    }
+
+    fn get_code_size(&self, address: Address) -> usize {
+        self.accounts.get(&address).map(|a| a.code.len()).unwrap_or(0)
+    }
+
+    fn set_code(&mut self, address: Address, code: &[u8]) {
+        let old = mem::replace(&mut self.account_mut(address).code, code.to_vec());
+        self.record(JournalEntry::Code(address, old));
+    }
+
+    fn get_nonce(&self, address: Address) -> Result<U256, VmError> {
+        Ok(self.accounts.get(&address).map(|a| a.nonce).unwrap_or_default())
+    }
+
+    fn set_nonce(&mut self, address: Address, nonce: U256) {
+        let old = mem::replace(&mut self.account_mut(address).nonce, nonce);
+        self.record(JournalEntry::Nonce(address, old));
+    }
+
+    fn create_account(&mut self, address: Address) {
+        let balance = self.get_balance(address);
+        let old = self.accounts.insert(
+            address,
+            Account {
+                balance,
+                ..Account::default()
+            },
+        );
+        match old {
+            Some(account) => self.record(JournalEntry::Replaced(address, account)),
+            None => self.record(JournalEntry::Created(address)),
+        }
+    }
+
+    fn suicide(&mut self, address: Address) -> bool {
+        let (suicided, balance) = match self.accounts.get_mut(&address) {
+            Some(account) => (
+                mem::replace(&mut account.suicided, true),
+                mem::replace(&mut account.balance, U256::zero()),
+            ),
+            None => return false,
+        };
+        self.record(JournalEntry::Suicided(address, suicided));
+        self.record(JournalEntry::Balance(address, balance));
+        true
+    }
+
+    fn has_suicided(&self, address: Address) -> bool {
+        self.accounts.get(&address).map_or(false, |a| a.suicided)
+    }
+
+    fn add_balance(&mut self, address: Address, value: U256) {
+        let account = self.account_mut(address);
+        let old = account.balance;
+        account.balance = old.saturating_add(value);
+        self.record(JournalEntry::Balance(address, old));
+    }
+
+    fn sub_balance(&mut self, address: Address, value: U256) {
+        let account = self.account_mut(address);
+        let old = account.balance;
+        debug_assert!(old >= value, "balance of {:?} is below {}", address, value);
+        account.balance = old.saturating_sub(value);
+        self.record(JournalEntry::Balance(address, old));
+    }
+
+    fn exists(&self, address: Address) -> bool {
+        self.accounts.contains_key(&address)
+    }
+
+    fn empty(&self, address: Address) -> bool {
+        match self.accounts.get(&address) {
+            Some(a) => a.balance.is_zero() && a.nonce.is_zero() && a.code.is_empty(),
+            None => true,
+        }
+    }
+
+    fn get_state(&self, address: Address, key: H256) -> H256 {
+        self.accounts
+            .get(&address)
+            .and_then(|a| a.storage.get(&key).cloned())
+            .unwrap_or_default()
+    }
+
+    fn set_state(&mut self, address: Address, key: H256, value: H256) {
+        let account = self.account_mut(address);
+        let old = if value.is_zero() {
+            account.storage.remove(&key)
+        } else {
+            account.storage.insert(key, value)
+        };
+        self.record(JournalEntry::Storage(address, key, old));
+    }
+
+    fn get_committed_state(&self, address: Address, key: H256) -> H256 {
+        self.committed
+            .get(&address)
+            .and_then(|s| s.get(&key).cloned())
+            .unwrap_or_default()
+    }
+
+    fn add_refund(&mut self, gas: u64) {
+        self.refund += gas as i64;
+    }
+
+    fn sub_refund(&mut self, gas: u64) {
+        self.refund -= gas as i64;
+    }
+
+    fn get_refund(&self) -> i64 {
+        self.refund
+    }
+
+    fn get_block_hash(&self, number: U256) -> H256 {
+        self.block_hashes.block_hash(number)
+    }
+
+    fn add_log(&mut self, log: Log) {
+        self.logs.push(log)
+    }
+
+    fn snapshot(&mut self) -> usize {
+        self.snapshots.push(Snapshot {
+            journal: self.journal.len(),
+            logs: self.logs.len(),
+            refund: self.refund,
+        });
+        self.snapshots.len() - 1
+    }
+
+    fn revert_to_snapshot(&mut self, id: usize) {
+        if id >= self.snapshots.len() {
+            return;
+        }
+        self.snapshots.truncate(id + 1);
+        if let Some(snapshot) = self.snapshots.pop() {
+            for entry in self.journal.split_off(snapshot.journal).into_iter().rev() {
+                self.undo(entry);
+            }
+            self.logs.truncate(snapshot.logs);
+            self.refund = snapshot.refund;
+        }
+    }
+
+    fn discard_snapshot(&mut self, id: usize) {
+        self.snapshots.truncate(id);
+        if self.snapshots.is_empty() {
+            self.journal.clear();
+        }
+    }
+
+    fn block(&self) -> &BlockEnv {
+        &self.block
+    }
+
+    fn tx(&self) -> &TxEnv {
+        &self.tx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_memory_host_revert_undoes_changes() {
+        let a = Address::from_low_u64_be(0xaa);
+        let b = Address::from_low_u64_be(0xbb);
+        let key = common::u256_to_h256(U256::one());
+        let mut host = InMemoryHost::new();
+        host.add_balance(a, U256::from(10));
+        host.set_state(a, key, common::u256_to_h256(U256::from(2)));
+
+        let outer = host.snapshot();
+        host.sub_balance(a, U256::from(4));
+        host.add_balance(b, U256::from(4));
+        host.set_state(a, key, H256::zero());
+        host.set_nonce(a, U256::one());
+        let inner = host.snapshot();
+        host.set_code(b, &[0x00]);
+        host.create_account(a);
+        host.suicide(b);
+        // A discarded snapshot keeps its changes until an outer one reverts.
+        host.discard_snapshot(inner);
+        assert_eq!(host.get_balance(a), U256::from(6));
+        assert!(host.has_suicided(b));
+
+        host.revert_to_snapshot(outer);
+        assert_eq!(host.get_balance(a), U256::from(10));
+        assert_eq!(host.get_state(a, key), common::u256_to_h256(U256::from(2)));
+        assert_eq!(host.get_nonce(a), Ok(U256::zero()));
+        assert!(!host.exists(b));
+        assert!(host.snapshots.is_empty());
+        assert!(host.journal.is_empty());
+    }
+
+    #[test]
+    fn test_in_memory_host_journal_only_under_snapshot() {
+        let mut host = InMemoryHost::new();
+        host.add_balance(Address::zero(), U256::one());
+        assert!(host.journal.is_empty());
+        let id = host.snapshot();
+        host.add_balance(Address::zero(), U256::one());
+        assert_eq!(host.journal.len(), 1);
+        host.discard_snapshot(id);
+        assert!(host.journal.is_empty());
+        assert_eq!(host.get_balance(Address::zero()), U256::from(2));
+    }
+
+    #[test]
+    fn test_in_memory_host_balance_saturates() {
+        let mut host = InMemoryHost::new();
+        host.add_balance(Address::zero(), U256::max_value());
+        host.add_balance(Address::zero(), U256::one());
+        assert_eq!(host.get_balance(Address::zero()), U256::max_value());
+    }
+}
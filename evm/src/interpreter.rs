@@ -1,96 +1,603 @@
+use super::abi;
+use super::common;
 use super::core;
+use super::err::VmError;
+use super::gas;
+use super::host::{Host, Log};
 use super::opcodes;
+use super::precompiled::NativeCall;
+use super::spec::Fork;
 use ethereum_types::*;
+use std::cmp;
+
+/// Outcome of running a piece of code to completion.
+#[derive(Debug, PartialEq)]
+pub enum ExecutionResult {
+    /// Execution stopped normally with the given return data and gas left.
+    Success(Vec<u8>, u64),
+    /// Execution was reverted with the given return data and gas left.
+    Revert(Vec<u8>, u64),
+    /// Execution halted exceptionally, consuming all gas.
+    Halt(VmError),
+}
+
+impl ExecutionResult {
+    /// Decodes the revert data into `Error(string)`, `Panic(uint256)` or one
+    /// of the custom errors in `abi`. Returns None unless execution reverted.
+    pub fn revert_reason(&self, abi: &abi::ErrorAbi) -> Option<abi::RevertReason> {
+        match self {
+            ExecutionResult::Revert(data, _) => Some(abi::decode_revert(data, abi)),
+            _ => None,
+        }
+    }
+}
+
+/// Where `execute` stopped: either the frame is done, or it needs a child
+/// frame run before it can go on.
+enum Step {
+    Done(ExecutionResult),
+    Enter(Box<Interpreter>, Resume),
+}
+
+/// What a suspended frame does with the result of its child.
+enum Resume {
+    Call { snapshot: Option<usize>, out_offset: usize, out_size: usize },
+    Create { snapshot: usize, address: Address },
+}
 
 pub struct Interpreter {
     pub context: core::EVMContext,
+    gas_limit: u64,
+    pc: u64,
+    snapshot: usize,
 }
 
 impl Interpreter {
     pub fn new(context: core::EVMContext) -> Self {
-        Interpreter { context: context }
+        let gas_limit = context.contract.cgas;
+        Interpreter {
+            context,
+            gas_limit,
+            pc: 0,
+            snapshot: 0,
+        }
+    }
+
+    /// Gas consumed so far by this interpreter's code.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_limit - self.context.contract.cgas
+    }
+
+    /// Runs the code to completion. If it reverts or halts, every state
+    /// change it made through `host` is rolled back and its logs are
+    /// dropped. Otherwise the logs are left in `context.logs` in the order
+    /// they were emitted, and the outermost frame also hands them to `host`
+    /// and adds the capped refund to the gas left.
+    ///
+    /// Child frames of CALL and CREATE are kept on a stack of their own
+    /// rather than run by recursion, so reaching the depth limit does not
+    /// depend on the size of the thread's stack.
+    pub fn run<H: Host>(&mut self, host: &mut H) -> ExecutionResult {
+        self.enter(host);
+        let mut frames: Vec<(Box<Interpreter>, Resume)> = Vec::new();
+        let mut returned = None;
+        loop {
+            let frame = match frames.last_mut() {
+                Some((frame, _)) => frame,
+                None => &mut *self,
+            };
+            let step = match returned.take() {
+                Some((child, resume, r)) => {
+                    frame.resume(host, child, resume, r).and_then(|_| frame.execute(host))
+                }
+                None => frame.execute(host),
+            };
+            let r = match step {
+                Ok(Step::Enter(mut child, resume)) => {
+                    child.enter(host);
+                    frames.push((child, resume));
+                    continue;
+                }
+                Ok(Step::Done(r)) => frame.exit(host, Ok(r)),
+                Err(e) => frame.exit(host, Err(e)),
+            };
+            match frames.pop() {
+                Some((child, resume)) => returned = Some((child, resume, r)),
+                None => return r,
+            }
+        }
+    }
+
+    /// Starts the frame from the beginning of its code, taking the snapshot
+    /// its changes are rolled back to if it fails.
+    fn enter<H: Host>(&mut self, host: &mut H) {
+        self.gas_limit = self.context.contract.cgas;
+        self.pc = 0;
+        self.snapshot = host.snapshot();
+    }
+
+    /// Ends the frame with the outcome of its code, keeping or rolling back
+    /// its changes.
+    fn exit<H: Host>(
+        &mut self,
+        host: &mut H,
+        r: Result<ExecutionResult, VmError>,
+    ) -> ExecutionResult {
+        let snapshot = self.snapshot;
+        match r {
+            Ok(r @ ExecutionResult::Revert(..)) => {
+                host.revert_to_snapshot(snapshot);
+                self.context.logs.clear();
+                r
+            }
+            Ok(mut r) => {
+                host.discard_snapshot(snapshot);
+                if self.context.depth == 0 {
+                    for log in &self.context.logs {
+                        host.add_log(log.clone());
+                    }
+                    if let ExecutionResult::Success(_, ref mut gas_left) = r {
+                        // The refund is paid once the transaction ends, up to
+                        // a fork-dependent share of the gas used (EIP-3529).
+                        let used = self.gas_limit - *gas_left;
+                        let refund = cmp::max(host.get_refund(), 0) as u64;
+                        let refund = cmp::min(refund, used / self.context.conf.max_refund_quotient);
+                        *gas_left += refund;
+                        self.context.contract.cgas = *gas_left;
+                    }
+                }
+                r
+            }
+            Err(e) => {
+                host.revert_to_snapshot(snapshot);
+                self.context.logs.clear();
+                self.context.contract.cgas = 0;
+                ExecutionResult::Halt(e)
+            }
+        }
     }
 
-    pub fn run(&mut self) {
+    fn execute<H: Host>(&mut self, host: &mut H) -> Result<Step, VmError> {
         let this = &mut *self;
-        let mut pc = 0;
+        let mut pc = this.pc;
         loop {
-            let op = this.context.contract.get_opcode(pc);
-            println!("Opcode: {}", op);
+            let op = this.context.contract.get_opcode(pc)?;
+            if !this.context.conf.fork.has_opcode(op) {
+                return Err(VmError::InvalidOpcode(op as u8));
+            }
+            let info = op.info();
+            this.context.stack.check(info.inputs, info.outputs)?;
+            if this.context.is_static
+                && (info.writes_state
+                    || (op == opcodes::OpCode::CALL && !this.context.stack.back(2)?.is_zero()))
+            {
+                return Err(VmError::StaticViolation);
+            }
+            let tier = op.gas_price_tier();
+            if !this.context.contract.use_gas(this.context.conf.tier_step_gas[tier.idx()]) {
+                return Err(VmError::OutOfGas);
+            }
+            let gas = gas::dynamic_gas(
+                &this.context.conf,
+                &op,
+                &this.context.stack,
+                this.context.memory.len(),
+            )?;
+            if !this.context.contract.use_gas(gas) {
+                return Err(VmError::OutOfGas);
+            }
+            let mut enter = None;
             match op {
                 opcodes::OpCode::STOP => {
-                    break
+                    let gas_left = this.context.contract.cgas;
+                    return Ok(Step::Done(ExecutionResult::Success(Vec::new(), gas_left)));
                 }
                 opcodes::OpCode::ADD => {
-                    let a = this.context.stack.pop();
-                    let b = this.context.stack.pop();
-                    this.context.stack.push(a + b);
-                }
-                opcodes::OpCode::MUL => {}
-                opcodes::OpCode::SUB => {}
-                opcodes::OpCode::DIV => {}
-                opcodes::OpCode::SDIV => {}
-                opcodes::OpCode::MOD => {}
-                opcodes::OpCode::SMOD => {}
-                opcodes::OpCode::ADDMOD => {}
-                opcodes::OpCode::MULMOD => {}
-                opcodes::OpCode::EXP => {}
-                opcodes::OpCode::SIGNEXTEND => {}
-                opcodes::OpCode::LT => {}
-                opcodes::OpCode::GT => {}
-                opcodes::OpCode::SLT => {}
-                opcodes::OpCode::SGT => {}
-                opcodes::OpCode::EQ => {}
-                opcodes::OpCode::ISZERO => {}
-                opcodes::OpCode::AND => {}
-                opcodes::OpCode::OR => {}
-                opcodes::OpCode::XOR => {}
-                opcodes::OpCode::NOT => {}
-                opcodes::OpCode::BYTE => {}
-                opcodes::OpCode::SHL => {}
-                opcodes::OpCode::SHR => {}
-                opcodes::OpCode::SAR => {}
-                opcodes::OpCode::SHA3 => {}
-                opcodes::OpCode::ADDRESS => {}
-                opcodes::OpCode::BALANCE => {}
-                opcodes::OpCode::ORIGIN => {}
-                opcodes::OpCode::CALLER => {}
-                opcodes::OpCode::CALLVALUE => {}
-                opcodes::OpCode::CALLDATALOAD => {}
-                opcodes::OpCode::CALLDATASIZE => {}
-                opcodes::OpCode::CALLDATACOPY => {}
-                opcodes::OpCode::CODESIZE => {}
-                opcodes::OpCode::CODECOPY => {}
-                opcodes::OpCode::GASPRICE => {}
-                opcodes::OpCode::EXTCODESIZE => {}
-                opcodes::OpCode::EXTCODECOPY => {}
-                opcodes::OpCode::RETURNDATASIZE => {}
-                opcodes::OpCode::RETURNDATACOPY => {}
-                opcodes::OpCode::EXTCODEHASH => {}
-                opcodes::OpCode::BLOCKHASH => {}
-                opcodes::OpCode::COINBASE => {}
-                opcodes::OpCode::TIMESTAMP => {}
-                opcodes::OpCode::NUMBER => {}
-                opcodes::OpCode::DIFFICULTY => {}
-                opcodes::OpCode::GASLIMIT => {}
-                opcodes::OpCode::POP => {}
-                opcodes::OpCode::MLOAD => {}
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(a.overflowing_add(b).0)?;
+                }
+                opcodes::OpCode::MUL => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(a.overflowing_mul(b).0)?;
+                }
+                opcodes::OpCode::SUB => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(a.overflowing_sub(b).0)?;
+                }
+                opcodes::OpCode::DIV => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(if b.is_zero() { U256::zero() } else { a / b })?;
+                }
+                opcodes::OpCode::SDIV => {
+                    let (a, neg_a) = common::sign_u256(this.context.stack.pop()?);
+                    let (b, neg_b) = common::sign_u256(this.context.stack.pop()?);
+                    let min = U256::one() << 255;
+                    this.context.stack.push(if b.is_zero() {
+                        U256::zero()
+                    } else if a == min && b == U256::one() && neg_b {
+                        // -2^255 / -1 overflows back to -2^255.
+                        min
+                    } else {
+                        common::set_sign(a / b, neg_a ^ neg_b)
+                    })?;
+                }
+                opcodes::OpCode::MOD => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(if b.is_zero() { U256::zero() } else { a % b })?;
+                }
+                opcodes::OpCode::SMOD => {
+                    let (a, neg_a) = common::sign_u256(this.context.stack.pop()?);
+                    let (b, _) = common::sign_u256(this.context.stack.pop()?);
+                    this.context.stack.push(if b.is_zero() {
+                        U256::zero()
+                    } else {
+                        common::set_sign(a % b, neg_a)
+                    })?;
+                }
+                opcodes::OpCode::ADDMOD => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    let c = this.context.stack.pop()?;
+                    this.context.stack.push(if c.is_zero() {
+                        U256::zero()
+                    } else {
+                        let r = (common::to_u512(a) + common::to_u512(b)) % common::to_u512(c);
+                        common::to_u256(r)
+                    })?;
+                }
+                opcodes::OpCode::MULMOD => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    let c = this.context.stack.pop()?;
+                    this.context.stack.push(if c.is_zero() {
+                        U256::zero()
+                    } else {
+                        let r = (common::to_u512(a) * common::to_u512(b)) % common::to_u512(c);
+                        common::to_u256(r)
+                    })?;
+                }
+                opcodes::OpCode::EXP => {
+                    let base = this.context.stack.pop()?;
+                    let power = this.context.stack.pop()?;
+                    this.context.stack.push(base.overflowing_pow(power).0)?;
+                }
+                opcodes::OpCode::SIGNEXTEND => {
+                    let back = this.context.stack.pop()?;
+                    let num = this.context.stack.pop()?;
+                    if back < U256::from(31) {
+                        let bit_position = (back.as_u64() * 8 + 7) as usize;
+                        let bit = num.bit(bit_position);
+                        let mask = (U256::one() << bit_position) - U256::one();
+                        this.context.stack.push(if bit { num | !mask } else { num & mask })?;
+                    } else {
+                        this.context.stack.push(num)?;
+                    }
+                }
+                opcodes::OpCode::LT => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(common::bool_to_u256(a < b))?;
+                }
+                opcodes::OpCode::GT => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(common::bool_to_u256(a > b))?;
+                }
+                opcodes::OpCode::SLT => {
+                    let (a, neg_a) = common::sign_u256(this.context.stack.pop()?);
+                    let (b, neg_b) = common::sign_u256(this.context.stack.pop()?);
+                    let is_positive_lt = a < b && !(neg_a | neg_b);
+                    let is_negative_lt = a > b && (neg_a & neg_b);
+                    let has_different_signs = neg_a && !neg_b;
+                    this.context.stack.push(common::bool_to_u256(
+                        is_positive_lt | is_negative_lt | has_different_signs,
+                    ))?;
+                }
+                opcodes::OpCode::SGT => {
+                    let (a, neg_a) = common::sign_u256(this.context.stack.pop()?);
+                    let (b, neg_b) = common::sign_u256(this.context.stack.pop()?);
+                    let is_positive_gt = a > b && !(neg_a | neg_b);
+                    let is_negative_gt = a < b && (neg_a & neg_b);
+                    let has_different_signs = !neg_a && neg_b;
+                    this.context.stack.push(common::bool_to_u256(
+                        is_positive_gt | is_negative_gt | has_different_signs,
+                    ))?;
+                }
+                opcodes::OpCode::EQ => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(common::bool_to_u256(a == b))?;
+                }
+                opcodes::OpCode::ISZERO => {
+                    let a = this.context.stack.pop()?;
+                    this.context.stack.push(common::bool_to_u256(a.is_zero()))?;
+                }
+                opcodes::OpCode::AND => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(a & b)?;
+                }
+                opcodes::OpCode::OR => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(a | b)?;
+                }
+                opcodes::OpCode::XOR => {
+                    let a = this.context.stack.pop()?;
+                    let b = this.context.stack.pop()?;
+                    this.context.stack.push(a ^ b)?;
+                }
+                opcodes::OpCode::NOT => {
+                    let a = this.context.stack.pop()?;
+                    this.context.stack.push(!a)?;
+                }
+                opcodes::OpCode::BYTE => {
+                    let th = this.context.stack.pop()?;
+                    let val = this.context.stack.pop()?;
+                    this.context.stack.push(if th >= U256::from(32) {
+                        U256::zero()
+                    } else {
+                        (val >> (8 * (31 - th.as_u64() as usize))) & U256::from(0xff)
+                    })?;
+                }
+                opcodes::OpCode::SHL => {
+                    let shift = this.context.stack.pop()?;
+                    let value = this.context.stack.pop()?;
+                    this.context.stack.push(if shift >= U256::from(256) {
+                        U256::zero()
+                    } else {
+                        value << (shift.as_u64() as usize)
+                    })?;
+                }
+                opcodes::OpCode::SHR => {
+                    let shift = this.context.stack.pop()?;
+                    let value = this.context.stack.pop()?;
+                    this.context.stack.push(if shift >= U256::from(256) {
+                        U256::zero()
+                    } else {
+                        value >> (shift.as_u64() as usize)
+                    })?;
+                }
+                opcodes::OpCode::SAR => {
+                    let shift = this.context.stack.pop()?;
+                    let value = this.context.stack.pop()?;
+                    let sign = value.bit(255);
+                    this.context.stack.push(if shift >= U256::from(256) {
+                        if sign {
+                            !U256::zero()
+                        } else {
+                            U256::zero()
+                        }
+                    } else {
+                        let shift = shift.as_u64() as usize;
+                        let shifted = value >> shift;
+                        if sign && shift > 0 {
+                            shifted | (!U256::zero() << (256 - shift))
+                        } else {
+                            shifted
+                        }
+                    })?;
+                }
+                opcodes::OpCode::SHA3 => {
+                    let offset = this.context.stack.pop()?;
+                    let size = common::mem_size(this.context.stack.pop()?)?;
+                    let hash = if size == 0 {
+                        common::keccak(&[])
+                    } else {
+                        let offset = common::mem_size(offset)?;
+                        this.context.memory.expand(offset + size)?;
+                        common::keccak(this.context.memory.get(offset, size)?)
+                    };
+                    this.context.stack.push(U256::from(&hash[..]))?;
+                }
+                opcodes::OpCode::ADDRESS => {
+                    let address = this.context.contract.address;
+                    this.context.stack.push(common::address_to_u256(address))?;
+                }
+                opcodes::OpCode::BALANCE => {
+                    let address = common::u256_to_address(this.context.stack.pop()?);
+                    this.access_account(address, this.context.conf.gas_balance)?;
+                    this.context.stack.push(host.get_balance(address))?;
+                }
+                opcodes::OpCode::ORIGIN => {
+                    this.context.stack.push(common::address_to_u256(host.tx().origin))?;
+                }
+                opcodes::OpCode::CALLER => {
+                    let caller = this.context.contract.caller;
+                    this.context.stack.push(common::address_to_u256(caller))?;
+                }
+                opcodes::OpCode::CALLVALUE => {
+                    let value = this.context.contract.value;
+                    this.context.stack.push(value)?;
+                }
+                opcodes::OpCode::CALLDATALOAD => {
+                    let start = this.context.stack.pop()?;
+                    let data = common::copy_data(&this.context.contract.input, start, 32);
+                    this.context.stack.push(U256::from(&data[..]))?;
+                }
+                opcodes::OpCode::CALLDATASIZE => {
+                    let size = this.context.contract.input.len();
+                    this.context.stack.push(U256::from(size))?;
+                }
+                opcodes::OpCode::CALLDATACOPY => {
+                    let mem_offset = this.context.stack.pop()?;
+                    let data_offset = this.context.stack.pop()?;
+                    let size = common::mem_size(this.context.stack.pop()?)?;
+                    let data = common::copy_data(&this.context.contract.input, data_offset, size);
+                    this.write_memory(mem_offset, &data)?;
+                }
+                opcodes::OpCode::CODESIZE => {
+                    let size = this.context.contract.code.len();
+                    this.context.stack.push(U256::from(size))?;
+                }
+                opcodes::OpCode::CODECOPY => {
+                    let mem_offset = this.context.stack.pop()?;
+                    let code_offset = this.context.stack.pop()?;
+                    let size = common::mem_size(this.context.stack.pop()?)?;
+                    let data = common::copy_data(&this.context.contract.code, code_offset, size);
+                    this.write_memory(mem_offset, &data)?;
+                }
+                opcodes::OpCode::GASPRICE => {
+                    this.context.stack.push(host.tx().gas_price)?;
+                }
+                opcodes::OpCode::EXTCODESIZE => {
+                    let address = common::u256_to_address(this.context.stack.pop()?);
+                    this.access_account(address, this.context.conf.gas_ext_code)?;
+                    this.context.stack.push(U256::from(host.get_code_size(address)))?;
+                }
+                opcodes::OpCode::EXTCODECOPY => {
+                    let address = common::u256_to_address(this.context.stack.pop()?);
+                    let mem_offset = this.context.stack.pop()?;
+                    let code_offset = this.context.stack.pop()?;
+                    let size = common::mem_size(this.context.stack.pop()?)?;
+                    this.access_account(address, this.context.conf.gas_ext_code)?;
+                    let data = common::copy_data(&host.get_code(address), code_offset, size);
+                    this.write_memory(mem_offset, &data)?;
+                }
+                opcodes::OpCode::RETURNDATASIZE => {
+                    let size = this.context.return_data.len();
+                    this.context.stack.push(U256::from(size))?;
+                }
+                opcodes::OpCode::RETURNDATACOPY => {
+                    let mem_offset = this.context.stack.pop()?;
+                    let data_offset = this.context.stack.pop()?;
+                    let size = this.context.stack.pop()?;
+                    // Unlike the other copies, reading past the end is an
+                    // exceptional halt rather than zero padding (EIP-211).
+                    let (end, overflow) = data_offset.overflowing_add(size);
+                    if overflow || end > U256::from(this.context.return_data.len()) {
+                        return Err(VmError::ReturnDataOutOfBounds);
+                    }
+                    let size = size.low_u64() as usize;
+                    let data = common::copy_data(&this.context.return_data, data_offset, size);
+                    this.write_memory(mem_offset, &data)?;
+                }
+                opcodes::OpCode::EXTCODEHASH => {
+                    let address = common::u256_to_address(this.context.stack.pop()?);
+                    this.access_account(address, this.context.conf.gas_ext_code_hash)?;
+                    // Accounts that do not exist, or are empty and so count as
+                    // non-existent since EIP-161, hash to zero (EIP-1052).
+                    let hash = if host.empty(address) {
+                        U256::zero()
+                    } else {
+                        common::h256_to_u256(host.get_code_hash(address))
+                    };
+                    this.context.stack.push(hash)?;
+                }
+                opcodes::OpCode::BLOCKHASH => {
+                    let number = this.context.stack.pop()?;
+                    // Only the 256 most recent blocks are available.
+                    let current = host.block().number;
+                    let hash = if number < current && current - number <= U256::from(256) {
+                        common::h256_to_u256(host.get_block_hash(number))
+                    } else {
+                        U256::zero()
+                    };
+                    this.context.stack.push(hash)?;
+                }
+                opcodes::OpCode::COINBASE => {
+                    this.context.stack.push(common::address_to_u256(host.block().coinbase))?;
+                }
+                opcodes::OpCode::TIMESTAMP => {
+                    this.context.stack.push(host.block().timestamp)?;
+                }
+                opcodes::OpCode::NUMBER => {
+                    this.context.stack.push(host.block().number)?;
+                }
+                opcodes::OpCode::DIFFICULTY => {
+                    this.context.stack.push(host.block().difficulty)?;
+                }
+                opcodes::OpCode::GASLIMIT => {
+                    this.context.stack.push(host.block().gas_limit)?;
+                }
+                opcodes::OpCode::CHAINID => {
+                    this.context.stack.push(host.block().chain_id)?;
+                }
+                opcodes::OpCode::SELFBALANCE => {
+                    let balance = host.get_balance(this.context.contract.address);
+                    this.context.stack.push(balance)?;
+                }
+                opcodes::OpCode::BASEFEE => {
+                    this.context.stack.push(host.block().base_fee)?;
+                }
+                opcodes::OpCode::POP => {
+                    this.context.stack.pop()?;
+                }
+                opcodes::OpCode::MLOAD => {
+                    let offset = common::mem_size(this.context.stack.pop()?)?;
+                    this.context.memory.expand(offset + 32)?;
+                    let word = U256::from(this.context.memory.get(offset, 32)?);
+                    this.context.stack.push(word)?;
+                }
                 opcodes::OpCode::MSTORE => {
-                    let offset = this.context.stack.pop();
-                    let word = this.context.stack.pop();
+                    let offset = this.context.stack.pop()?;
+                    let word = this.context.stack.pop()?;
                     let word = &<[u8; 32]>::from(word)[..];
-                    this.context.memory.expand(offset.as_u64() as usize + 32);
-                    this.context.memory.set(offset.as_u64() as usize, word);
-                }
-                opcodes::OpCode::MSTORE8 => {}
-                opcodes::OpCode::SLOAD => {}
-                opcodes::OpCode::SSTORE => {}
-                opcodes::OpCode::JUMP => {}
-                opcodes::OpCode::JUMPI => {}
-                opcodes::OpCode::PC => {}
-                opcodes::OpCode::MSIZE => {}
-                opcodes::OpCode::GAS => {}
+                    let offset = common::mem_size(offset)?;
+                    this.context.memory.expand(offset + 32)?;
+                    this.context.memory.set(offset, word)?;
+                }
+                opcodes::OpCode::MSTORE8 => {
+                    let offset = common::mem_size(this.context.stack.pop()?)?;
+                    let byte = this.context.stack.pop()?.low_u64() as u8;
+                    this.context.memory.expand(offset + 1)?;
+                    this.context.memory.set(offset, &[byte])?;
+                }
+                opcodes::OpCode::SLOAD => {
+                    let key = common::u256_to_h256(this.context.stack.pop()?);
+                    let address = this.context.contract.address;
+                    let cold = this.context.access.touch_slot(address, key);
+                    let gas = if this.context.conf.access_lists && cold {
+                        this.context.conf.gas_cold_sload
+                    } else {
+                        this.context.conf.gas_sload
+                    };
+                    this.use_gas(gas)?;
+                    let value = host.get_state(address, key);
+                    this.context.stack.push(common::h256_to_u256(value))?;
+                }
+                opcodes::OpCode::SSTORE => {
+                    let key = common::u256_to_h256(this.context.stack.pop()?);
+                    let value = common::u256_to_h256(this.context.stack.pop()?);
+                    this.sstore(host, key, value)?;
+                }
+                opcodes::OpCode::JUMP => {
+                    let dest = this.context.stack.pop()?;
+                    if !this.context.contract.valid_jumpdest(dest) {
+                        return Err(VmError::InvalidJump);
+                    }
+                    pc = dest.low_u64();
+                    continue;
+                }
+                opcodes::OpCode::JUMPI => {
+                    let dest = this.context.stack.pop()?;
+                    let cond = this.context.stack.pop()?;
+                    if !cond.is_zero() {
+                        if !this.context.contract.valid_jumpdest(dest) {
+                            return Err(VmError::InvalidJump);
+                        }
+                        pc = dest.low_u64();
+                        continue;
+                    }
+                }
+                opcodes::OpCode::PC => {
+                    this.context.stack.push(U256::from(pc))?;
+                }
+                opcodes::OpCode::MSIZE => {
+                    this.context.stack.push(U256::from(this.context.memory.len()))?;
+                }
+                opcodes::OpCode::GAS => {
+                    this.context.stack.push(U256::from(this.context.contract.cgas))?;
+                }
                 opcodes::OpCode::JUMPDEST => {}
+                opcodes::OpCode::PUSH0 => {
+                    this.context.stack.push(U256::zero())?;
+                }
                 opcodes::OpCode::PUSH1
                 | opcodes::OpCode::PUSH2
                 | opcodes::OpCode::PUSH3
@@ -123,67 +630,578 @@ impl Interpreter {
                 | opcodes::OpCode::PUSH30
                 | opcodes::OpCode::PUSH31
                 | opcodes::OpCode::PUSH32 => {
-                    pc += 1;
-                    let n = op as u8 - opcodes::OpCode::PUSH1 as u8 + 1;
-                    let r = U256::from(&this.context.contract.code[pc as usize..(pc+n as u64) as usize]);
-                    pc += n as u64 - 1;
-                    this.context.stack.push(r);
-                }
-                opcodes::OpCode::DUP1 => {}
-                opcodes::OpCode::DUP2 => {}
-                opcodes::OpCode::DUP3 => {}
-                opcodes::OpCode::DUP4 => {}
-                opcodes::OpCode::DUP5 => {}
-                opcodes::OpCode::DUP6 => {}
-                opcodes::OpCode::DUP7 => {}
-                opcodes::OpCode::DUP8 => {}
-                opcodes::OpCode::DUP9 => {}
-                opcodes::OpCode::DUP10 => {}
-                opcodes::OpCode::DUP11 => {}
-                opcodes::OpCode::DUP12 => {}
-                opcodes::OpCode::DUP13 => {}
-                opcodes::OpCode::DUP14 => {}
-                opcodes::OpCode::DUP15 => {}
-                opcodes::OpCode::DUP16 => {}
-                opcodes::OpCode::SWAP1 => {}
-                opcodes::OpCode::SWAP2 => {}
-                opcodes::OpCode::SWAP3 => {}
-                opcodes::OpCode::SWAP4 => {}
-                opcodes::OpCode::SWAP5 => {}
-                opcodes::OpCode::SWAP6 => {}
-                opcodes::OpCode::SWAP7 => {}
-                opcodes::OpCode::SWAP8 => {}
-                opcodes::OpCode::SWAP9 => {}
-                opcodes::OpCode::SWAP10 => {}
-                opcodes::OpCode::SWAP11 => {}
-                opcodes::OpCode::SWAP12 => {}
-                opcodes::OpCode::SWAP13 => {}
-                opcodes::OpCode::SWAP14 => {}
-                opcodes::OpCode::SWAP15 => {}
-                opcodes::OpCode::SWAP16 => {}
-                opcodes::OpCode::LOG0 => {}
-                opcodes::OpCode::LOG1 => {}
-                opcodes::OpCode::LOG2 => {}
-                opcodes::OpCode::LOG3 => {}
-                opcodes::OpCode::LOG4 => {}
-                opcodes::OpCode::CREATE => {}
-                opcodes::OpCode::CALL => {}
-                opcodes::OpCode::CALLCODE => {}
+                    let n = info.immediate;
+                    // Immediates running past the end of the code read as zero.
+                    let mut r = [0u8; 32];
+                    for i in 0..n {
+                        r[32 - n + i] = this.context.contract.get_byte(pc + 1 + i as u64);
+                    }
+                    pc += n as u64;
+                    this.context.stack.push(U256::from(&r[..]))?;
+                }
+                opcodes::OpCode::DUP1
+                | opcodes::OpCode::DUP2
+                | opcodes::OpCode::DUP3
+                | opcodes::OpCode::DUP4
+                | opcodes::OpCode::DUP5
+                | opcodes::OpCode::DUP6
+                | opcodes::OpCode::DUP7
+                | opcodes::OpCode::DUP8
+                | opcodes::OpCode::DUP9
+                | opcodes::OpCode::DUP10
+                | opcodes::OpCode::DUP11
+                | opcodes::OpCode::DUP12
+                | opcodes::OpCode::DUP13
+                | opcodes::OpCode::DUP14
+                | opcodes::OpCode::DUP15
+                | opcodes::OpCode::DUP16 => {
+                    let n = op as u8 - opcodes::OpCode::DUP1 as u8 + 1;
+                    this.context.stack.dup(n as usize)?;
+                }
+                opcodes::OpCode::SWAP1
+                | opcodes::OpCode::SWAP2
+                | opcodes::OpCode::SWAP3
+                | opcodes::OpCode::SWAP4
+                | opcodes::OpCode::SWAP5
+                | opcodes::OpCode::SWAP6
+                | opcodes::OpCode::SWAP7
+                | opcodes::OpCode::SWAP8
+                | opcodes::OpCode::SWAP9
+                | opcodes::OpCode::SWAP10
+                | opcodes::OpCode::SWAP11
+                | opcodes::OpCode::SWAP12
+                | opcodes::OpCode::SWAP13
+                | opcodes::OpCode::SWAP14
+                | opcodes::OpCode::SWAP15
+                | opcodes::OpCode::SWAP16 => {
+                    let n = op as u8 - opcodes::OpCode::SWAP1 as u8 + 1;
+                    this.context.stack.swap(n as usize + 1)?;
+                }
+                opcodes::OpCode::LOG0
+                | opcodes::OpCode::LOG1
+                | opcodes::OpCode::LOG2
+                | opcodes::OpCode::LOG3
+                | opcodes::OpCode::LOG4 => {
+                    let offset = this.context.stack.pop()?;
+                    let size = common::mem_size(this.context.stack.pop()?)?;
+                    let n = (op as u8 - opcodes::OpCode::LOG0 as u8) as usize;
+                    let mut topics = Vec::with_capacity(n);
+                    for _ in 0..n {
+                        topics.push(common::u256_to_h256(this.context.stack.pop()?));
+                    }
+                    let data = if size == 0 {
+                        Vec::new()
+                    } else {
+                        let offset = common::mem_size(offset)?;
+                        this.context.memory.expand(offset + size)?;
+                        Vec::from(this.context.memory.get(offset, size)?)
+                    };
+                    this.context.logs.push(Log {
+                        address: this.context.contract.address,
+                        topics,
+                        data,
+                    });
+                }
+                opcodes::OpCode::CREATE => {
+                    enter = this.create(host, op)?;
+                }
+                opcodes::OpCode::CALL | opcodes::OpCode::CALLCODE => {
+                    enter = this.call(host, op)?;
+                }
                 opcodes::OpCode::RETURN => {
-                    let init_off = this.context.stack.pop().as_u64() as usize;
-                    let init_size = this.context.stack.pop().as_u64() as usize;
-                    let r = this.context.memory.get(init_off, init_size);
-                    this.context.return_data = Vec::from(r);
-                    break;
-                }
-                opcodes::OpCode::DELEGATECALL => {}
-                opcodes::OpCode::CREATE2 => {}
-                opcodes::OpCode::REVERT => {}
-                opcodes::OpCode::STATICCALL => {}
-                opcodes::OpCode::SUICIDE => {}
+                    let r = this.return_slice()?;
+                    this.context.return_data = r.clone();
+                    let gas_left = this.context.contract.cgas;
+                    return Ok(Step::Done(ExecutionResult::Success(r, gas_left)));
+                }
+                opcodes::OpCode::DELEGATECALL => {
+                    enter = this.call(host, op)?;
+                }
+                opcodes::OpCode::CREATE2 => {
+                    enter = this.create(host, op)?;
+                }
+                opcodes::OpCode::REVERT => {
+                    let r = this.return_slice()?;
+                    this.context.return_data = r.clone();
+                    let gas_left = this.context.contract.cgas;
+                    return Ok(Step::Done(ExecutionResult::Revert(r, gas_left)));
+                }
+                opcodes::OpCode::STATICCALL => {
+                    enter = this.call(host, op)?;
+                }
+                opcodes::OpCode::INVALID => {
+                    return Err(VmError::InvalidOpcode(op as u8));
+                }
+                opcodes::OpCode::SUICIDE => {
+                    let beneficiary = common::u256_to_address(this.context.stack.pop()?);
+                    this.selfdestruct(host, beneficiary)?;
+                    let gas_left = this.context.contract.cgas;
+                    return Ok(Step::Done(ExecutionResult::Success(Vec::new(), gas_left)));
+                }
             }
             pc += 1;
+            if let Some((child, resume)) = enter {
+                this.pc = pc;
+                return Ok(Step::Enter(child, resume));
+            }
+        }
+    }
+
+    fn use_gas(&mut self, gas: u64) -> Result<(), VmError> {
+        if !self.context.contract.use_gas(gas) {
+            return Err(VmError::OutOfGas);
+        }
+        Ok(())
+    }
+
+    /// Charges for and performs SSTORE, updating the refund counter.
+    fn sstore<H: Host>(&mut self, host: &mut H, key: H256, value: H256) -> Result<(), VmError> {
+        let conf = &self.context.conf;
+        let address = self.context.contract.address;
+        if conf.sstore_net_metering && self.context.contract.cgas <= conf.gas_sstore_sentry {
+            return Err(VmError::OutOfGas);
+        }
+        let mut gas = 0;
+        if self.context.access.touch_slot(address, key) && conf.access_lists {
+            gas += conf.gas_cold_sload;
+        }
+        let current = host.get_state(address, key);
+        if !conf.sstore_net_metering {
+            if current.is_zero() && !value.is_zero() {
+                gas += conf.gas_sstore_set;
+            } else {
+                if !current.is_zero() && value.is_zero() {
+                    host.add_refund(conf.gas_sstore_clear_refund);
+                }
+                gas += conf.gas_sstore_reset;
+            }
+        } else if current == value {
+            gas += conf.gas_sload;
+        } else {
+            // EIP-2200 net gas metering.
+            let original = host.get_committed_state(address, key);
+            if original == current {
+                if original.is_zero() {
+                    gas += conf.gas_sstore_set;
+                } else {
+                    if value.is_zero() {
+                        host.add_refund(conf.gas_sstore_clear_refund);
+                    }
+                    gas += conf.gas_sstore_reset;
+                }
+            } else {
+                gas += conf.gas_sload;
+                if !original.is_zero() {
+                    if current.is_zero() {
+                        host.sub_refund(conf.gas_sstore_clear_refund);
+                    } else if value.is_zero() {
+                        host.add_refund(conf.gas_sstore_clear_refund);
+                    }
+                }
+                if original == value {
+                    if original.is_zero() {
+                        host.add_refund(conf.gas_sstore_set - conf.gas_sload);
+                    } else {
+                        host.add_refund(conf.gas_sstore_reset - conf.gas_sload);
+                    }
+                }
+            }
+        }
+        self.use_gas(gas)?;
+        host.set_state(address, key, value);
+        Ok(())
+    }
+
+    /// Starts CALL, CALLCODE, DELEGATECALL or STATICCALL and returns the
+    /// child frame to run, if any. Native contracts run here, and failing to
+    /// enter the child, because the depth limit is reached or the balance is
+    /// too low, is not an error for the caller; the gas set aside for the
+    /// child is simply returned.
+    fn call<H: Host>(
+        &mut self,
+        host: &mut H,
+        op: opcodes::OpCode,
+    ) -> Result<Option<(Box<Interpreter>, Resume)>, VmError> {
+        let requested = self.context.stack.pop()?;
+        let to = common::u256_to_address(self.context.stack.pop()?);
+        let value = match op {
+            opcodes::OpCode::CALL | opcodes::OpCode::CALLCODE => self.context.stack.pop()?,
+            _ => U256::zero(),
+        };
+        let in_offset = self.context.stack.pop()?;
+        let in_size = common::mem_size(self.context.stack.pop()?)?;
+        let out_offset = self.context.stack.pop()?;
+        let out_size = common::mem_size(self.context.stack.pop()?)?;
+        let input = if in_size == 0 {
+            Vec::new()
+        } else {
+            let in_offset = common::mem_size(in_offset)?;
+            self.context.memory.expand(in_offset + in_size)?;
+            Vec::from(self.context.memory.get(in_offset, in_size)?)
+        };
+        let out_offset = if out_size == 0 { 0 } else { common::mem_size(out_offset)? };
+        if out_size != 0 {
+            self.context.memory.expand(out_offset + out_size)?;
+        }
+
+        let conf = self.context.conf.clone();
+        let mut gas = conf.gas_call;
+        if conf.access_lists && self.context.touch_address(to) {
+            gas = conf.gas_cold_account_access;
+        }
+        if !value.is_zero() {
+            gas += conf.gas_call_value;
+        }
+        if op == opcodes::OpCode::CALL {
+            let new_account = if conf.empty_account_is_new {
+                !value.is_zero() && host.empty(to)
+            } else {
+                !host.exists(to)
+            };
+            if new_account {
+                gas += conf.gas_new_account;
+            }
+        }
+        self.use_gas(gas)?;
+
+        // EIP-150: the caller always keeps at least 1/64 of its gas.
+        let available = self.context.contract.cgas;
+        let mut child_gas = if conf.call_gas_cap {
+            let cap = available - available / 64;
+            if requested > U256::from(cap) {
+                cap
+            } else {
+                requested.low_u64()
+            }
+        } else {
+            if requested > U256::from(available) {
+                return Err(VmError::OutOfGas);
+            }
+            requested.low_u64()
+        };
+        self.use_gas(child_gas)?;
+        if !value.is_zero() {
+            child_gas += conf.gas_call_stipend;
+        }
+
+        self.context.return_data = Vec::new();
+        let address = self.context.contract.address;
+        if self.context.depth >= conf.call_depth_limit
+            || (!value.is_zero() && host.get_balance(address) < value)
+        {
+            self.context.contract.cgas += child_gas;
+            self.context.stack.push(U256::zero())?;
+            return Ok(None);
+        }
+
+        // The child frame rolls back its own changes, so a snapshot is only
+        // needed for the value transfer and for native contracts.
+        let transfer = op == opcodes::OpCode::CALL && !value.is_zero();
+        let native = conf.precompiles.contains(&to);
+        let snapshot = if transfer || native { Some(host.snapshot()) } else { None };
+        if transfer {
+            host.sub_balance(address, value);
+            host.add_balance(to, value);
+        }
+
+        let (caller, callee, callee_value) = match op {
+            opcodes::OpCode::CALLCODE => (address, address, value),
+            opcodes::OpCode::DELEGATECALL => {
+                (self.context.contract.caller, address, self.context.contract.value)
+            }
+            _ => (address, to, value),
+        };
+        let is_static = self.context.is_static || op == opcodes::OpCode::STATICCALL;
+        if !native {
+            let mut context = self.context.child();
+            context.is_static = is_static;
+            context.contract.cgas = child_gas;
+            context.contract.input = input;
+            context.contract.code = host.get_code(to);
+            context.contract.code_hash = host.get_code_hash(to);
+            context.contract.caller = caller;
+            context.contract.address = callee;
+            context.contract.value = callee_value;
+            let resume = Resume::Call {
+                snapshot,
+                out_offset,
+                out_size,
+            };
+            return Ok(Some((Box::new(Interpreter::new(context)), resume)));
+        }
+
+        let gas = conf.precompiles.required_gas(&to, &input);
+        let (success, output) = if gas > child_gas {
+            (false, Vec::new())
+        } else {
+            let call = NativeCall {
+                caller,
+                address: callee,
+                value: callee_value,
+                is_static,
+            };
+            let mut logs = Vec::new();
+            match conf.precompiles.run(&to, &call, &input, host, &mut logs) {
+                Ok(output) => {
+                    self.context.contract.cgas += child_gas - gas;
+                    self.context.logs.append(&mut logs);
+                    (true, output)
+                }
+                Err(_) => (false, Vec::new()),
+            }
+        };
+        self.finish_call(host, snapshot, out_offset, out_size, success, output)?;
+        Ok(None)
+    }
+
+    /// Picks up after the child frame started by `call` or `create` ends
+    /// with `r`, pushing its outcome onto the stack.
+    fn resume<H: Host>(
+        &mut self,
+        host: &mut H,
+        mut child: Box<Interpreter>,
+        resume: Resume,
+        r: ExecutionResult,
+    ) -> Result<(), VmError> {
+        match resume {
+            Resume::Call {
+                snapshot,
+                out_offset,
+                out_size,
+            } => {
+                let (success, output) = match r {
+                    ExecutionResult::Success(data, gas_left) => {
+                        self.context.contract.cgas += gas_left;
+                        self.context.access = child.context.access;
+                        self.context.created = child.context.created;
+                        self.context.logs.append(&mut child.context.logs);
+                        (true, data)
+                    }
+                    ExecutionResult::Revert(data, gas_left) => {
+                        self.context.contract.cgas += gas_left;
+                        (false, data)
+                    }
+                    ExecutionResult::Halt(_) => (false, Vec::new()),
+                };
+                self.finish_call(host, snapshot, out_offset, out_size, success, output)
+            }
+            Resume::Create { snapshot, address } => {
+                let homestead = self.context.conf.fork >= Fork::Homestead;
+                let created = match r {
+                    ExecutionResult::Success(code, gas_left) => {
+                        let deposit = code.len() as u64 * self.context.conf.gas_code_deposit;
+                        if code.len() > self.context.conf.max_code_size {
+                            false
+                        } else if gas_left < deposit {
+                            // Before Homestead running out of gas for the
+                            // deposit left an account without code instead
+                            // of failing.
+                            if !homestead {
+                                self.context.contract.cgas += gas_left;
+                                self.context.access = child.context.access;
+                                self.context.created = child.context.created;
+                                self.context.logs.append(&mut child.context.logs);
+                            }
+                            !homestead
+                        } else {
+                            host.set_code(address, &code);
+                            self.context.contract.cgas += gas_left - deposit;
+                            self.context.access = child.context.access;
+                            self.context.created = child.context.created;
+                            self.context.logs.append(&mut child.context.logs);
+                            true
+                        }
+                    }
+                    ExecutionResult::Revert(data, gas_left) => {
+                        self.context.contract.cgas += gas_left;
+                        self.context.return_data = data;
+                        false
+                    }
+                    ExecutionResult::Halt(_) => false,
+                };
+                if !created {
+                    host.revert_to_snapshot(snapshot);
+                    return self.context.stack.push(U256::zero());
+                }
+                host.discard_snapshot(snapshot);
+                self.context.stack.push(common::address_to_u256(address))
+            }
+        }
+    }
+
+    /// Ends a call: keeps or rolls back the value transfer, copies the
+    /// output to memory and pushes whether the call succeeded.
+    fn finish_call<H: Host>(
+        &mut self,
+        host: &mut H,
+        snapshot: Option<usize>,
+        out_offset: usize,
+        out_size: usize,
+        success: bool,
+        output: Vec<u8>,
+    ) -> Result<(), VmError> {
+        match snapshot {
+            Some(id) if success => host.discard_snapshot(id),
+            Some(id) => host.revert_to_snapshot(id),
+            None => {}
+        }
+        let n = cmp::min(out_size, output.len());
+        if n != 0 {
+            self.context.memory.set(out_offset, &output[..n])?;
+        }
+        self.context.return_data = output;
+        self.context.stack.push(common::bool_to_u256(success))
+    }
+
+    /// Starts CREATE or CREATE2 and returns the child frame running the init
+    /// code. If the account cannot be created, pushes zero and returns None.
+    fn create<H: Host>(
+        &mut self,
+        host: &mut H,
+        op: opcodes::OpCode,
+    ) -> Result<Option<(Box<Interpreter>, Resume)>, VmError> {
+        let value = self.context.stack.pop()?;
+        let offset = self.context.stack.pop()?;
+        let size = common::mem_size(self.context.stack.pop()?)?;
+        let salt = match op {
+            opcodes::OpCode::CREATE2 => Some(common::u256_to_h256(self.context.stack.pop()?)),
+            _ => None,
+        };
+        let init_code = if size == 0 {
+            Vec::new()
+        } else {
+            let offset = common::mem_size(offset)?;
+            self.context.memory.expand(offset + size)?;
+            Vec::from(self.context.memory.get(offset, size)?)
+        };
+
+        self.context.return_data = Vec::new();
+        let address = self.context.contract.address;
+        let nonce = host.get_nonce(address)?;
+        if self.context.depth >= self.context.conf.call_depth_limit
+            || host.get_balance(address) < value
+            || nonce >= U256::from(u64::max_value())
+        {
+            self.context.stack.push(U256::zero())?;
+            return Ok(None);
         }
+
+        let conf = self.context.conf.clone();
+        let available = self.context.contract.cgas;
+        let child_gas = if conf.call_gas_cap { available - available / 64 } else { available };
+        self.use_gas(child_gas)?;
+
+        let code_hash = common::keccak(&init_code);
+        let new_address = match salt {
+            Some(salt) => common::create2_address(address, salt, code_hash),
+            None => common::create_address(address, nonce),
+        };
+        host.set_nonce(address, nonce + U256::one());
+        self.context.touch_address(new_address);
+        // Creating over an account with code or a nonce consumes the gas
+        // set aside for the child.
+        if !host.get_nonce(new_address)?.is_zero() || host.get_code_size(new_address) != 0 {
+            self.context.stack.push(U256::zero())?;
+            return Ok(None);
+        }
+
+        let snapshot = host.snapshot();
+        host.create_account(new_address);
+        if conf.empty_account_is_new {
+            host.set_nonce(new_address, U256::one());
+        }
+        host.sub_balance(address, value);
+        host.add_balance(new_address, value);
+
+        let mut context = self.context.child();
+        context.created.insert(new_address);
+        context.contract.caller = address;
+        context.contract.address = new_address;
+        context.contract.value = value;
+        context.contract.code = init_code;
+        context.contract.code_hash = code_hash;
+        context.contract.cgas = child_gas;
+        let resume = Resume::Create {
+            snapshot,
+            address: new_address,
+        };
+        Ok(Some((Box::new(Interpreter::new(context)), resume)))
+    }
+
+    /// Sends the whole balance to `beneficiary` and schedules the account
+    /// for deletion. Under EIP-6780 only contracts created in the same
+    /// transaction are deleted; others just lose their balance.
+    fn selfdestruct<H: Host>(&mut self, host: &mut H, beneficiary: Address) -> Result<(), VmError> {
+        let cold = self.context.touch_address(beneficiary);
+        let conf = &self.context.conf;
+        let address = self.context.contract.address;
+        let balance = host.get_balance(address);
+        let mut gas = conf.gas_selfdestruct;
+        if conf.access_lists && cold {
+            gas += conf.gas_cold_account_access;
+        }
+        // Creating the beneficiary is charged from EIP-150 on.
+        if conf.call_gas_cap {
+            let new_account = if conf.empty_account_is_new {
+                !balance.is_zero() && host.empty(beneficiary)
+            } else {
+                !host.exists(beneficiary)
+            };
+            if new_account {
+                gas += conf.gas_new_account;
+            }
+        }
+        let refund = conf.gas_selfdestruct_refund;
+        let delete = !conf.selfdestruct_only_new || self.context.created.contains(&address);
+        self.use_gas(gas)?;
+
+        if !delete {
+            host.sub_balance(address, balance);
+            host.add_balance(beneficiary, balance);
+            return Ok(());
+        }
+        if !host.has_suicided(address) {
+            host.add_refund(refund);
+        }
+        // Sending to itself burns the balance, as the account is deleted.
+        host.add_balance(beneficiary, balance);
+        host.suicide(address);
+        Ok(())
+    }
+
+    /// Charges the EIP-2929 surcharge for a cold account on top of the `warm`
+    /// cost already paid for the instruction.
+    fn access_account(&mut self, address: Address, warm: u64) -> Result<(), VmError> {
+        let cold = self.context.touch_address(address);
+        if self.context.conf.access_lists && cold {
+            let gas = self.context.conf.gas_cold_account_access - warm;
+            self.use_gas(gas)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `data` to memory at `offset` for the copy instructions. Empty
+    /// copies never touch memory, whatever the offset.
+    fn write_memory(&mut self, offset: U256, data: &[u8]) -> Result<(), VmError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let offset = common::mem_size(offset)?;
+        self.context.memory.expand(offset + data.len())?;
+        self.context.memory.set(offset, data)
+    }
+
+    /// Pops an offset and a size and reads that region of memory for RETURN
+    /// and REVERT.
+    fn return_slice(&mut self) -> Result<Vec<u8>, VmError> {
+        let offset = self.context.stack.pop()?;
+        let size = common::mem_size(self.context.stack.pop()?)?;
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+        let offset = common::mem_size(offset)?;
+        self.context.memory.expand(offset + size)?;
+        Ok(Vec::from(self.context.memory.get(offset, size)?))
     }
 }
 
@@ -192,6 +1210,10 @@ impl Interpreter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::host::{BlockEnv, InMemoryHost};
+    use crate::precompiled::NativeContract;
+    use crate::spec;
+    use std::rc::Rc;
     #[test]
     fn test_interpreter_execute_0x01() {
         let mut context = core::EVMContext::new();
@@ -204,8 +1226,1582 @@ mod tests {
             opcodes::OpCode::RETURN as u8,
         ];
         let mut it = Interpreter::new(context);
-        it.run();
+        let r = it.run(&mut InMemoryHost::new());
+        // 4 * PUSH1 + MSTORE + one word of memory
+        assert_eq!(r, ExecutionResult::Success(it.context.return_data.clone(), 20982));
+        assert_eq!(it.gas_used(), 18);
         let r = U256::from_big_endian(&it.context.return_data[..]);
         assert_eq!(r, U256::from(10));
     }
+
+    fn run_and_return_word(code: &[u8]) -> U256 {
+        let mut context = core::EVMContext::new();
+        context.contract.code = code.to_vec();
+        context.contract.code.extend_from_slice(&[
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ]);
+        let mut it = Interpreter::new(context);
+        if let ExecutionResult::Success(..) = it.run(&mut InMemoryHost::new()) {
+        } else {
+            panic!("execution failed");
+        }
+        U256::from_big_endian(&it.context.return_data[..])
+    }
+
+    #[test]
+    fn test_interpreter_arithmetic_wrapping() {
+        // 0 - 1 wraps to 2^256 - 1.
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::SUB as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), !U256::zero());
+
+        // (2^256 - 1) * 2 wraps to 2^256 - 2.
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::NOT as u8,
+            opcodes::OpCode::MUL as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), !U256::one());
+
+        // 10 / 0 == 0
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 10,
+            opcodes::OpCode::DIV as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), U256::zero());
+
+        // (2^256 - 1 + 2) % 7 == 3 without losing the carry.
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 7,
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::NOT as u8,
+            opcodes::OpCode::ADDMOD as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), U256::from(3));
+
+        // 2 ** 256 wraps to 0.
+        let code = vec![
+            opcodes::OpCode::PUSH2 as u8, 1, 0,
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::EXP as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), U256::zero());
+    }
+
+    #[test]
+    fn test_interpreter_signed_arithmetic() {
+        // -8 / 3 == -2
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 3,
+            opcodes::OpCode::PUSH1 as u8, 7,
+            opcodes::OpCode::NOT as u8,
+            opcodes::OpCode::SDIV as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), !U256::one());
+
+        // -8 % 3 == -2
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 3,
+            opcodes::OpCode::PUSH1 as u8, 7,
+            opcodes::OpCode::NOT as u8,
+            opcodes::OpCode::SMOD as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), !U256::one());
+
+        // -1 < 1
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::NOT as u8,
+            opcodes::OpCode::SLT as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), U256::one());
+
+        // SIGNEXTEND(0, 0xff) == -1
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0xff,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::SIGNEXTEND as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), !U256::zero());
+    }
+
+    #[test]
+    fn test_interpreter_bitwise() {
+        // BYTE(31, 0x1234) == 0x34
+        let code = vec![
+            opcodes::OpCode::PUSH2 as u8, 0x12, 0x34,
+            opcodes::OpCode::PUSH1 as u8, 31,
+            opcodes::OpCode::BYTE as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), U256::from(0x34));
+
+        // 1 << 255 >> 255 == 1
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 255,
+            opcodes::OpCode::SHL as u8,
+            opcodes::OpCode::PUSH1 as u8, 255,
+            opcodes::OpCode::SHR as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), U256::one());
+
+        // -16 >> 2 (arithmetic) == -4
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 15,
+            opcodes::OpCode::NOT as u8,
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::SAR as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), !U256::from(3));
+    }
+
+    #[test]
+    fn test_interpreter_halts_instead_of_panicking() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![opcodes::OpCode::ADD as u8];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::StackUnderflow));
+        assert_eq!(it.context.contract.cgas, 0);
+
+        // A huge MSTORE offset must not be truncated or panic.
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH32 as u8,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            opcodes::OpCode::MSTORE as u8,
+        ];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::MemoryLimit));
+    }
+
+    #[test]
+    fn test_interpreter_memory() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = return_words(&[
+            // MSTORE8 keeps the low byte and grows memory by a whole word.
+            &[
+                opcodes::OpCode::PUSH2 as u8, 0x12, 0x34,
+                opcodes::OpCode::PUSH1 as u8, 0x5f,
+                opcodes::OpCode::MSTORE8 as u8,
+                opcodes::OpCode::MSIZE as u8,
+            ],
+            &[opcodes::OpCode::PUSH1 as u8, 0x40, opcodes::OpCode::MLOAD as u8],
+            // Reading also expands memory.
+            &[
+                opcodes::OpCode::PUSH1 as u8, 0x90,
+                opcodes::OpCode::MLOAD as u8,
+                opcodes::OpCode::MSIZE as u8,
+                opcodes::OpCode::SWAP1 as u8,
+                opcodes::OpCode::POP as u8,
+            ],
+        ]);
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut InMemoryHost::new());
+        assert_eq!(
+            returned_words(&it),
+            vec![U256::from(0x60), U256::from(0x34), U256::from(0xc0)]
+        );
+    }
+
+    #[test]
+    fn test_interpreter_memory_limit() {
+        let mload = |offset: u8| {
+            let mut context = core::EVMContext::new();
+            Rc::make_mut(&mut context.conf).max_memory = 64;
+            context.contract.code = vec![
+                opcodes::OpCode::PUSH1 as u8, offset,
+                opcodes::OpCode::MLOAD as u8,
+            ];
+            context.contract.cgas = 100_000;
+            let mut it = Interpreter::new(context);
+            it.run(&mut InMemoryHost::new())
+        };
+        match mload(32) {
+            ExecutionResult::Success(..) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        // The cap holds however much gas is available.
+        assert_eq!(mload(33), ExecutionResult::Halt(VmError::MemoryLimit));
+    }
+
+    #[test]
+    fn test_interpreter_revert() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x2a,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 31,
+            opcodes::OpCode::REVERT as u8,
+        ];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Revert(vec![0x2a], 20982));
+    }
+
+    #[test]
+    fn test_interpreter_out_of_gas() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::MUL as u8,
+        ];
+        // Enough for both pushes but not for MUL.
+        context.contract.cgas = 10;
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::OutOfGas));
+        assert_eq!(it.gas_used(), 10);
+    }
+
+    #[test]
+    fn test_interpreter_sha3() {
+        // keccak256 of the empty string.
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::SHA3 as u8,
+        ];
+        let expected = U256::from(
+            &[
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+                0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+                0x5d, 0x85, 0xa4, 0x70,
+            ][..],
+        );
+        assert_eq!(run_and_return_word(&code), expected);
+
+        let mut context = core::EVMContext::new();
+        context.contract.code = code;
+        let mut it = Interpreter::new(context);
+        it.run(&mut InMemoryHost::new());
+        // 2 * PUSH1 + SHA3 of zero words
+        assert_eq!(it.gas_used(), 36);
+    }
+
+    #[test]
+    fn test_interpreter_jumpi() {
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0,       // 0
+            opcodes::OpCode::PUSH1 as u8, 0xff,    // 2: not validated when not taken
+            opcodes::OpCode::JUMPI as u8,          // 4
+            opcodes::OpCode::PUSH1 as u8, 1,       // 5
+            opcodes::OpCode::PUSH1 as u8, 12,      // 7
+            opcodes::OpCode::JUMPI as u8,          // 9
+            opcodes::OpCode::PUSH1 as u8, 0x42,    // 10: skipped
+            opcodes::OpCode::JUMPDEST as u8,       // 12
+            opcodes::OpCode::PC as u8,             // 13
+        ];
+        assert_eq!(run_and_return_word(&code), U256::from(13));
+    }
+
+    #[test]
+    fn test_interpreter_jump_into_push_data() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 3,
+            opcodes::OpCode::JUMP as u8,
+            opcodes::OpCode::PUSH1 as u8, opcodes::OpCode::JUMPDEST as u8,
+        ];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::InvalidJump));
+    }
+
+    #[test]
+    fn test_interpreter_dup_swap_pop() {
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::PUSH1 as u8, 3,
+            opcodes::OpCode::DUP3 as u8,    // 1 2 3 1
+            opcodes::OpCode::SWAP2 as u8,   // 1 1 3 2
+            opcodes::OpCode::POP as u8,     // 1 1 3
+            opcodes::OpCode::SUB as u8,     // 1 2
+            opcodes::OpCode::SUB as u8,     // 1
+        ];
+        assert_eq!(run_and_return_word(&code), U256::from(1));
+    }
+
+    #[test]
+    fn test_interpreter_stack_limits() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![opcodes::OpCode::DUP1 as u8];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::StackUnderflow));
+
+        // PC; JUMPDEST; DUP1; PUSH1 1; JUMP grows the stack forever.
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PC as u8,
+            opcodes::OpCode::JUMPDEST as u8,
+            opcodes::OpCode::DUP1 as u8,
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::JUMP as u8,
+        ];
+        context.contract.cgas = 1_000_000;
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::StackOverflow));
+        assert_eq!(it.context.stack.len(), 1024);
+    }
+
+    #[test]
+    fn test_interpreter_invalid_opcodes() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![opcodes::OpCode::INVALID as u8];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::InvalidOpcode(0xfe)));
+
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![opcodes::OpCode::PUSH1 as u8, 0x01, 0x0c];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::InvalidOpcode(0x0c)));
+
+        // Undefined bytes in push data or after STOP are never executed.
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x0c,
+            opcodes::OpCode::STOP as u8,
+            0x21,
+        ];
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Success(Vec::new(), 20997));
+    }
+
+    #[test]
+    fn test_interpreter_fork_opcodes() {
+        let code = vec![
+            opcodes::OpCode::PUSH0 as u8,
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::SHL as u8,
+        ];
+        assert_eq!(run_and_return_word(&code), U256::zero());
+
+        let mut context = core::EVMContext::new();
+        context.conf = Rc::new(core::EVMConf::with_fork(spec::Fork::London));
+        context.contract.code = code.clone();
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::InvalidOpcode(0x5f)));
+
+        let mut context = core::EVMContext::new();
+        context.conf = Rc::new(core::EVMConf::with_fork(spec::Fork::Byzantium));
+        context.contract.code = code[1..].to_vec();
+        context.contract.code.insert(0, opcodes::OpCode::PUSH1 as u8);
+        context.contract.code.insert(1, 0);
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::InvalidOpcode(0x1b)));
+    }
+
+    #[test]
+    fn test_interpreter_sload_sstore() {
+        let mut host = InMemoryHost::new();
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x2a,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SLOAD as u8,
+            opcodes::OpCode::PUSH1 as u8, 0x00,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        let r = it.run(&mut host);
+        let mut expected = vec![0u8; 32];
+        expected[31] = 0x2a;
+        // 7 * PUSH1 + MSTORE + memory + cold SSTORE of a new slot + warm SLOAD
+        assert_eq!(r, ExecutionResult::Success(expected, 100_000 - 24 - 2100 - 20000 - 100));
+        let key = common::u256_to_h256(U256::one());
+        assert_eq!(host.get_state(Address::zero(), key), common::u256_to_h256(U256::from(0x2a)));
+    }
+
+    #[test]
+    fn test_interpreter_sstore_refunds() {
+        let key = common::u256_to_h256(U256::one());
+        let mut host = InMemoryHost::new();
+        host.set_state(Address::zero(), key, common::u256_to_h256(U256::one()));
+        host.commit();
+
+        // Clearing a slot refunds the clear refund.
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x00,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+        let mut context = core::EVMContext::new();
+        context.contract.code = code.clone();
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        // The refund is capped at a fifth of the gas used.
+        assert_eq!(it.gas_used(), 6 + 2100 + 2900 - (6 + 2100 + 2900) / 5);
+        assert_eq!(host.get_refund(), 4800);
+
+        // Restoring the original value takes the refund back and refunds the
+        // difference to a no-op write.
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+        let mut context = core::EVMContext::new();
+        context.contract.code = code;
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        assert_eq!(it.gas_used(), 6 + 2100 + 100 - (6 + 2100 + 100) / 5);
+        assert_eq!(host.get_refund(), 2900 - 100);
+
+        // Frontier rules ignore the original value.
+        let mut host = InMemoryHost::new();
+        let mut context = core::EVMContext::new();
+        context.conf = Rc::new(core::EVMConf::with_fork(spec::Fork::Frontier));
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        assert_eq!(it.gas_used(), 6 + 20000);
+    }
+
+    #[test]
+    fn test_interpreter_refund_cap() {
+        let clear_slot = |fork: spec::Fork| {
+            let key = common::u256_to_h256(U256::one());
+            let mut host = InMemoryHost::new();
+            host.set_state(Address::zero(), key, common::u256_to_h256(U256::one()));
+            host.commit();
+            let mut context = core::EVMContext::new();
+            context.conf = Rc::new(core::EVMConf::with_fork(fork));
+            context.contract.code = vec![
+                opcodes::OpCode::PUSH1 as u8, 0x00,
+                opcodes::OpCode::PUSH1 as u8, 0x01,
+                opcodes::OpCode::SSTORE as u8,
+            ];
+            context.contract.cgas = 100_000;
+            let mut it = Interpreter::new(context);
+            let r = it.run(&mut host);
+            assert_eq!(r, ExecutionResult::Success(Vec::new(), 100_000 - it.gas_used()));
+            (it.gas_used(), host.get_refund())
+        };
+        // Berlin refunds 15000 for the clear, capped at half the gas used.
+        assert_eq!(clear_slot(spec::Fork::Berlin), (5006 - 5006 / 2, 15000));
+        // London refunds 4800, capped at a fifth (EIP-3529).
+        assert_eq!(clear_slot(spec::Fork::London), (5006 - 5006 / 5, 4800));
+    }
+
+    #[test]
+    fn test_interpreter_sstore_sentry() {
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+        context.contract.cgas = 2306;
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut InMemoryHost::new()), ExecutionResult::Halt(VmError::OutOfGas));
+    }
+
+    /// Code that stores each value pushed by `ops` in consecutive words of
+    /// memory and returns them.
+    fn return_words(ops: &[&[u8]]) -> Vec<u8> {
+        let mut code = Vec::new();
+        for (i, op) in ops.iter().enumerate() {
+            code.extend_from_slice(op);
+            code.extend_from_slice(&[
+                opcodes::OpCode::PUSH1 as u8, (32 * i) as u8,
+                opcodes::OpCode::MSTORE as u8,
+            ]);
+        }
+        let size = 32 * ops.len() as u16;
+        code.extend_from_slice(&[
+            opcodes::OpCode::PUSH2 as u8, (size >> 8) as u8, size as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ]);
+        code
+    }
+
+    fn returned_words(it: &Interpreter) -> Vec<U256> {
+        it.context.return_data.chunks(32).map(U256::from_big_endian).collect()
+    }
+
+    #[test]
+    fn test_interpreter_call_context() {
+        let mut host = InMemoryHost::new();
+        host.tx.origin = Address::from_low_u64_be(0x0f);
+        host.tx.gas_price = U256::from(7);
+        let mut context = core::EVMContext::new();
+        context.contract.address = Address::from_low_u64_be(0xaa);
+        context.contract.caller = Address::from_low_u64_be(0xbb);
+        context.contract.value = U256::from(5);
+        context.contract.code = return_words(&[
+            &[opcodes::OpCode::ADDRESS as u8],
+            &[opcodes::OpCode::CALLER as u8],
+            &[opcodes::OpCode::CALLVALUE as u8],
+            &[opcodes::OpCode::ORIGIN as u8],
+            &[opcodes::OpCode::GASPRICE as u8],
+            &[opcodes::OpCode::CODESIZE as u8],
+        ]);
+        let code_size = context.contract.code.len();
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        assert_eq!(
+            returned_words(&it),
+            vec![
+                U256::from(0xaa),
+                U256::from(0xbb),
+                U256::from(5),
+                U256::from(0x0f),
+                U256::from(7),
+                U256::from(code_size),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpreter_block_env() {
+        let mut host = InMemoryHost::new();
+        host.block = BlockEnv {
+            coinbase: Address::from_low_u64_be(0xc0),
+            timestamp: U256::from(1_600_000_000),
+            number: U256::from(100),
+            difficulty: U256::from(3),
+            gas_limit: U256::from(30_000_000),
+            chain_id: U256::from(1337),
+            base_fee: U256::from(7),
+        };
+        host.accounts.entry(Address::from_low_u64_be(0xaa)).or_default().balance = U256::from(9);
+        let it = run_at(
+            &mut host,
+            return_words(&[
+                &[opcodes::OpCode::COINBASE as u8],
+                &[opcodes::OpCode::TIMESTAMP as u8],
+                &[opcodes::OpCode::NUMBER as u8],
+                &[opcodes::OpCode::DIFFICULTY as u8],
+                &[opcodes::OpCode::GASLIMIT as u8],
+                &[opcodes::OpCode::CHAINID as u8],
+                &[opcodes::OpCode::SELFBALANCE as u8],
+                &[opcodes::OpCode::BASEFEE as u8],
+            ]),
+            100_000,
+        );
+        assert_eq!(
+            returned_words(&it),
+            vec![
+                U256::from(0xc0),
+                U256::from(1_600_000_000),
+                U256::from(100),
+                U256::from(3),
+                U256::from(30_000_000),
+                U256::from(1337),
+                U256::from(9),
+                U256::from(7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interpreter_blockhash() {
+        let mut host = InMemoryHost::new();
+        host.block.number = U256::from(1000);
+        for n in 700..1001 {
+            host.block_hashes.insert(U256::from(n), H256::from_low_u64_be(n));
+        }
+        let blockhash = |n: u16| -> Vec<u8> {
+            vec![
+                opcodes::OpCode::PUSH2 as u8, (n >> 8) as u8, n as u8,
+                opcodes::OpCode::BLOCKHASH as u8,
+            ]
+        };
+        let it = run_at(
+            &mut host,
+            return_words(&[&blockhash(999), &blockhash(744), &blockhash(743), &blockhash(1000)]),
+            100_000,
+        );
+        // Blocks outside [number - 256, number) hash to zero.
+        assert_eq!(
+            returned_words(&it),
+            vec![U256::from(999), U256::from(744), U256::zero(), U256::zero()]
+        );
+    }
+
+    #[test]
+    fn test_interpreter_calldata() {
+        let mut context = core::EVMContext::new();
+        context.contract.input = vec![1, 2, 3, 4];
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::CALLDATALOAD as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::CALLDATASIZE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::MSTORE as u8,
+            // Copy 4 bytes from offset 2 to memory 64: two bytes of zero padding.
+            opcodes::OpCode::PUSH1 as u8, 4,
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::PUSH1 as u8, 64,
+            opcodes::OpCode::CALLDATACOPY as u8,
+            opcodes::OpCode::PUSH1 as u8, 96,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut InMemoryHost::new());
+        let data = &it.context.return_data;
+        assert_eq!(&data[..4], &[2, 3, 4, 0]);
+        assert!(data[4..32].iter().all(|b| *b == 0));
+        assert_eq!(U256::from_big_endian(&data[32..64]), U256::from(4));
+        assert_eq!(&data[64..68], &[3, 4, 0, 0]);
+    }
+
+    #[test]
+    fn test_interpreter_ext_code() {
+        let mut host = InMemoryHost::new();
+        let code = vec![opcodes::OpCode::PUSH1 as u8, 1];
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code = code.clone();
+        // An existing but empty account hashes to zero like a missing one.
+        host.accounts.entry(Address::from_low_u64_be(0xcc)).or_default();
+        host.accounts.entry(Address::from_low_u64_be(0xdd)).or_default().balance = U256::one();
+        let code_hash = common::h256_to_u256(common::keccak(&code));
+        let it = run_at(
+            &mut host,
+            return_words(&[
+                &[opcodes::OpCode::PUSH1 as u8, 0xbb, opcodes::OpCode::EXTCODESIZE as u8],
+                &[opcodes::OpCode::PUSH1 as u8, 0xbb, opcodes::OpCode::EXTCODEHASH as u8],
+                &[opcodes::OpCode::PUSH1 as u8, 0xcc, opcodes::OpCode::EXTCODEHASH as u8],
+                &[opcodes::OpCode::PUSH1 as u8, 0xee, opcodes::OpCode::EXTCODEHASH as u8],
+                &[opcodes::OpCode::PUSH1 as u8, 0xdd, opcodes::OpCode::EXTCODEHASH as u8],
+            ]),
+            100_000,
+        );
+        let empty_hash = common::h256_to_u256(common::keccak(&[]));
+        assert_eq!(
+            returned_words(&it),
+            vec![U256::from(2), code_hash, U256::zero(), U256::zero(), empty_hash]
+        );
+
+        let it = run_at(
+            &mut host,
+            vec![
+                opcodes::OpCode::PUSH1 as u8, 4,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0xbb,
+                opcodes::OpCode::EXTCODECOPY as u8,
+                opcodes::OpCode::PUSH1 as u8, 32,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::RETURN as u8,
+            ],
+            100_000,
+        );
+        assert_eq!(&it.context.return_data[..4], &[opcodes::OpCode::PUSH1 as u8, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_interpreter_account_access_gas() {
+        // The first access to an account is cold (EIP-2929).
+        let code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0xbb,
+            opcodes::OpCode::BALANCE as u8,
+            opcodes::OpCode::PUSH1 as u8, 0xbb,
+            opcodes::OpCode::EXTCODESIZE as u8,
+        ];
+        let it = run_at(&mut InMemoryHost::new(), code.clone(), 100_000);
+        assert_eq!(it.gas_used(), 3 + 2600 + 3 + 100);
+
+        let mut context = core::EVMContext::new();
+        context.conf = Rc::new(core::EVMConf::with_fork(spec::Fork::Istanbul));
+        context.contract.code = code;
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut InMemoryHost::new());
+        assert_eq!(it.gas_used(), 3 + 700 + 3 + 700);
+    }
+
+    #[test]
+    fn test_interpreter_returndata() {
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x2a,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        let copy_then_return = |size: u8| {
+            let mut code = call_code(opcodes::OpCode::STATICCALL, 0xbb, 0);
+            code.extend_from_slice(&[
+                opcodes::OpCode::RETURNDATASIZE as u8,
+                opcodes::OpCode::PUSH1 as u8, 64,
+                opcodes::OpCode::MSTORE as u8,
+                opcodes::OpCode::PUSH1 as u8, size,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 32,
+                opcodes::OpCode::RETURNDATACOPY as u8,
+                opcodes::OpCode::PUSH1 as u8, 96,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::RETURN as u8,
+            ]);
+            code
+        };
+        let it = run_at(&mut host, copy_then_return(32), 100_000);
+        assert_eq!(
+            returned_words(&it),
+            vec![U256::from(0x2a), U256::from(0x2a), U256::from(32)]
+        );
+
+        // Reading past the end of the return data halts (EIP-211).
+        let mut context = core::EVMContext::new();
+        context.contract.code = copy_then_return(33);
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut host), ExecutionResult::Halt(VmError::ReturnDataOutOfBounds));
+    }
+
+    /// Code that calls `to` with all available gas, leaving the success flag
+    /// on the stack and the first word of output at memory offset 0.
+    fn call_code(op: opcodes::OpCode, to: u8, value: u8) -> Vec<u8> {
+        let mut code = vec![
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 0,
+        ];
+        if op == opcodes::OpCode::CALL || op == opcodes::OpCode::CALLCODE {
+            code.extend_from_slice(&[opcodes::OpCode::PUSH1 as u8, value]);
+        }
+        code.extend_from_slice(&[
+            opcodes::OpCode::PUSH1 as u8, to,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::NOT as u8,
+            op as u8,
+        ]);
+        code
+    }
+
+    /// Appends code returning the output word and the success flag.
+    fn return_output_and_flag(code: &mut Vec<u8>) {
+        code.extend_from_slice(&[
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 64,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ]);
+    }
+
+    fn run_at(host: &mut InMemoryHost, code: Vec<u8>, gas: u64) -> Interpreter {
+        let mut context = core::EVMContext::new();
+        context.contract.address = Address::from_low_u64_be(0xaa);
+        context.contract.code = code;
+        context.contract.cgas = gas;
+        let mut it = Interpreter::new(context);
+        it.run(host);
+        it
+    }
+
+    fn output_words(it: &Interpreter) -> (U256, U256) {
+        let data = &it.context.return_data;
+        (U256::from_big_endian(&data[..32]), U256::from_big_endian(&data[32..64]))
+    }
+
+    #[test]
+    fn test_interpreter_call_returns_output() {
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x2a,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        for op in &[
+            opcodes::OpCode::CALL,
+            opcodes::OpCode::CALLCODE,
+            opcodes::OpCode::DELEGATECALL,
+            opcodes::OpCode::STATICCALL,
+        ] {
+            let mut code = call_code(*op, 0xbb, 0);
+            return_output_and_flag(&mut code);
+            let it = run_at(&mut host, code, 100_000);
+            assert_eq!(output_words(&it), (U256::from(0x2a), U256::one()));
+        }
+    }
+
+    #[test]
+    fn test_interpreter_call_storage_context() {
+        let caller = Address::from_low_u64_be(0xaa);
+        let callee = Address::from_low_u64_be(0xbb);
+        let key = H256::zero();
+        let one = common::u256_to_h256(U256::one());
+        let callee_code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(callee).or_default().code = callee_code.clone();
+        run_at(&mut host, call_code(opcodes::OpCode::CALL, 0xbb, 0), 100_000);
+        assert_eq!(host.get_state(callee, key), one);
+        assert!(host.get_state(caller, key).is_zero());
+
+        for op in &[opcodes::OpCode::CALLCODE, opcodes::OpCode::DELEGATECALL] {
+            let mut host = InMemoryHost::new();
+            host.accounts.entry(callee).or_default().code = callee_code.clone();
+            run_at(&mut host, call_code(*op, 0xbb, 0), 100_000);
+            assert!(host.get_state(callee, key).is_zero());
+            assert_eq!(host.get_state(caller, key), one);
+        }
+    }
+
+    #[test]
+    fn test_interpreter_call_value() {
+        let caller = Address::from_low_u64_be(0xaa);
+        let callee = Address::from_low_u64_be(0xbb);
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(caller).or_default().balance = U256::from(100);
+
+        let mut code = call_code(opcodes::OpCode::CALL, 0xbb, 10);
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 100_000);
+        assert_eq!(output_words(&it).1, U256::one());
+        assert_eq!(host.get_balance(caller), U256::from(90));
+        assert_eq!(host.get_balance(callee), U256::from(10));
+
+        // Sending more than the balance fails without entering the callee.
+        let mut code = call_code(opcodes::OpCode::CALL, 0xbb, 200);
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 100_000);
+        assert_eq!(output_words(&it).1, U256::zero());
+        assert_eq!(host.get_balance(caller), U256::from(90));
+    }
+
+    #[test]
+    fn test_interpreter_call_stipend() {
+        // A value-bearing call with no gas still gets the stipend.
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xaa)).or_default().balance = U256::one();
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code = vec![
+            opcodes::OpCode::GAS as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        let mut code = vec![
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 0xbb,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::CALL as u8,
+        ];
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 100_000);
+        assert_eq!(output_words(&it), (U256::from(2300 - 2), U256::one()));
+    }
+
+    #[test]
+    fn test_interpreter_call_gas_forwarding() {
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code = vec![
+            opcodes::OpCode::GAS as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        let mut code = call_code(opcodes::OpCode::CALL, 0xbb, 0);
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 100_000);
+        // 7 * PUSH1 + NOT + memory + cold account access, then all but one
+        // 64th of the rest.
+        let available = 100_000 - 24 - 3 - 2600;
+        let forwarded = available - available / 64;
+        assert_eq!(output_words(&it), (U256::from(forwarded - 2), U256::one()));
+    }
+
+    #[test]
+    fn test_interpreter_call_depth_limit() {
+        let mut host = InMemoryHost::new();
+        let mut context = core::EVMContext::new();
+        context.depth = 1024;
+        context.contract.code = call_code(opcodes::OpCode::CALL, 0xbb, 0);
+        return_output_and_flag(&mut context.contract.code);
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        assert_eq!(output_words(&it), (U256::zero(), U256::zero()));
+        // The gas set aside for the callee comes back, leaving the cost of
+        // the call itself and of returning.
+        assert_eq!(it.gas_used(), 24 + 3 + 2600 + 15);
+    }
+
+    #[test]
+    fn test_interpreter_call_depth_stack() {
+        // Child frames do not recurse on the native stack, so a contract
+        // calling itself down to the depth limit fits in a thread's default
+        // stack.
+        let frames = std::thread::spawn(|| {
+            let mut host = InMemoryHost::new();
+            let code = vec![
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::SLOAD as u8,
+                opcodes::OpCode::PUSH1 as u8, 1,
+                opcodes::OpCode::ADD as u8,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::SSTORE as u8,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0xaa,
+                opcodes::OpCode::GAS as u8,
+                opcodes::OpCode::CALL as u8,
+                opcodes::OpCode::STOP as u8,
+            ];
+            let address = Address::from_low_u64_be(0xaa);
+            host.accounts.entry(address).or_default().code = code.clone();
+            // Each level keeps a 64th of its gas, so the outermost frame
+            // needs about e^16 times what the innermost one spends.
+            run_at(&mut host, code, 1 << 40);
+            U256::from_big_endian(&host.get_state(address, H256::zero())[..])
+        })
+        .join()
+        .unwrap();
+        // Depths 0 through 1024 each run once; the last CALL is refused.
+        assert_eq!(frames, U256::from(1025));
+    }
+
+    /// Code that creates a contract from `init` (at most 32 bytes) and
+    /// returns the pushed address.
+    fn create_code(op: opcodes::OpCode, init: &[u8]) -> Vec<u8> {
+        let mut code = vec![opcodes::OpCode::PUSH1 as u8 + init.len() as u8 - 1];
+        code.extend_from_slice(init);
+        code.extend_from_slice(&[
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+        ]);
+        if op == opcodes::OpCode::CREATE2 {
+            code.extend_from_slice(&[opcodes::OpCode::PUSH1 as u8, 0x5a]);
+        }
+        code.extend_from_slice(&[
+            opcodes::OpCode::PUSH1 as u8, init.len() as u8,
+            opcodes::OpCode::PUSH1 as u8, 32 - init.len() as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            op as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ]);
+        code
+    }
+
+    /// Init code deploying the single byte 0xff.
+    const DEPLOY_FF: [u8; 10] = [
+        opcodes::OpCode::PUSH1 as u8, 0xff,
+        opcodes::OpCode::PUSH1 as u8, 0,
+        opcodes::OpCode::MSTORE as u8,
+        opcodes::OpCode::PUSH1 as u8, 1,
+        opcodes::OpCode::PUSH1 as u8, 31,
+        opcodes::OpCode::RETURN as u8,
+    ];
+
+    #[test]
+    fn test_interpreter_create() {
+        let sender = Address::from_low_u64_be(0xaa);
+        let mut host = InMemoryHost::new();
+        let it = run_at(&mut host, create_code(opcodes::OpCode::CREATE, &DEPLOY_FF), 1_000_000);
+        let created = common::create_address(sender, U256::zero());
+        assert_eq!(
+            U256::from_big_endian(&it.context.return_data),
+            common::address_to_u256(created)
+        );
+        assert_eq!(host.get_code(created), vec![0xff]);
+        assert_eq!(host.get_nonce(created), Ok(U256::one()));
+        assert_eq!(host.get_nonce(sender), Ok(U256::one()));
+
+        // The next CREATE uses the incremented nonce.
+        let it = run_at(&mut host, create_code(opcodes::OpCode::CREATE, &DEPLOY_FF), 1_000_000);
+        assert_eq!(
+            U256::from_big_endian(&it.context.return_data),
+            common::address_to_u256(common::create_address(sender, U256::one()))
+        );
+    }
+
+    #[test]
+    fn test_interpreter_create2() {
+        let sender = Address::from_low_u64_be(0xaa);
+        let mut host = InMemoryHost::new();
+        let it = run_at(&mut host, create_code(opcodes::OpCode::CREATE2, &DEPLOY_FF), 1_000_000);
+        let salt = common::u256_to_h256(U256::from(0x5a));
+        let created = common::create2_address(sender, salt, common::keccak(&DEPLOY_FF));
+        assert_eq!(
+            U256::from_big_endian(&it.context.return_data),
+            common::address_to_u256(created)
+        );
+        assert_eq!(host.get_code(created), vec![0xff]);
+
+        // Creating at the same address again collides.
+        let it = run_at(&mut host, create_code(opcodes::OpCode::CREATE2, &DEPLOY_FF), 1_000_000);
+        assert!(U256::from_big_endian(&it.context.return_data).is_zero());
+        assert_eq!(host.get_nonce(sender), Ok(U256::from(2)));
+    }
+
+    #[test]
+    fn test_interpreter_create_code_size_limit() {
+        // Init code returning 0x6001 zero bytes.
+        let init = [
+            opcodes::OpCode::PUSH2 as u8, 0x60, 0x01,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        let mut host = InMemoryHost::new();
+        let it = run_at(&mut host, create_code(opcodes::OpCode::CREATE, &init), 10_000_000);
+        assert!(U256::from_big_endian(&it.context.return_data).is_zero());
+
+        // One byte less fits.
+        let init = [
+            opcodes::OpCode::PUSH2 as u8, 0x60, 0x00,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::RETURN as u8,
+        ];
+        let it = run_at(&mut host, create_code(opcodes::OpCode::CREATE, &init), 10_000_000);
+        let created = common::create_address(Address::from_low_u64_be(0xaa), U256::one());
+        assert_eq!(
+            U256::from_big_endian(&it.context.return_data),
+            common::address_to_u256(created)
+        );
+        assert_eq!(host.get_code_size(created), 0x6000);
+    }
+
+    #[test]
+    fn test_interpreter_static_violations() {
+        let writes: Vec<Vec<u8>> = vec![
+            vec![
+                opcodes::OpCode::PUSH1 as u8, 1,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::SSTORE as u8,
+            ],
+            vec![
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::LOG0 as u8,
+            ],
+            create_code(opcodes::OpCode::CREATE, &DEPLOY_FF),
+            create_code(opcodes::OpCode::CREATE2, &DEPLOY_FF),
+            vec![
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::SUICIDE as u8,
+            ],
+            call_code(opcodes::OpCode::CALL, 0xcc, 1),
+        ];
+        for code in writes {
+            let mut context = core::EVMContext::new();
+            context.is_static = true;
+            context.contract.code = code;
+            context.contract.cgas = 100_000;
+            let mut it = Interpreter::new(context);
+            let mut host = InMemoryHost::new();
+            host.accounts.entry(Address::zero()).or_default().balance = U256::one();
+            assert_eq!(it.run(&mut host), ExecutionResult::Halt(VmError::StaticViolation));
+        }
+
+        // Calls without value and reads are fine.
+        let mut context = core::EVMContext::new();
+        context.is_static = true;
+        context.contract.code = call_code(opcodes::OpCode::CALL, 0xcc, 0);
+        context.contract.code.extend_from_slice(&[
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::SLOAD as u8,
+        ]);
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        let r = it.run(&mut InMemoryHost::new());
+        assert_eq!(r, ExecutionResult::Success(Vec::new(), 100_000 - 24 - 3 - 2600 - 3 - 2100));
+    }
+
+    #[test]
+    fn test_interpreter_staticcall_is_inherited() {
+        // 0xbb calls 0xcc, which writes to storage. Under STATICCALL the
+        // write fails even though 0xbb uses a plain CALL.
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code = {
+            let mut code = call_code(opcodes::OpCode::CALL, 0xcc, 0);
+            code.extend_from_slice(&[
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::MSTORE as u8,
+                opcodes::OpCode::PUSH1 as u8, 32,
+                opcodes::OpCode::PUSH1 as u8, 0,
+                opcodes::OpCode::RETURN as u8,
+            ]);
+            code
+        };
+        host.accounts.entry(Address::from_low_u64_be(0xcc)).or_default().code = vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+        let mut code = call_code(opcodes::OpCode::STATICCALL, 0xbb, 0);
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 1_000_000);
+        assert!(host.get_state(Address::from_low_u64_be(0xcc), H256::zero()).is_zero());
+        // 0xbb itself succeeds and reports that its inner call failed.
+        assert_eq!(output_words(&it), (U256::zero(), U256::one()));
+
+        host.accounts.get_mut(&Address::from_low_u64_be(0xbb)).unwrap().code =
+            call_code(opcodes::OpCode::CALL, 0xcc, 0);
+        run_at(&mut host, call_code(opcodes::OpCode::CALL, 0xbb, 0), 1_000_000);
+        assert!(!host.get_state(Address::from_low_u64_be(0xcc), H256::zero()).is_zero());
+    }
+
+    /// Code that stores 1 at slot 0 and then ends with `last`.
+    fn store_then(last: opcodes::OpCode) -> Vec<u8> {
+        vec![
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::SSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 0x2a,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 32,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            last as u8,
+        ]
+    }
+
+    #[test]
+    fn test_interpreter_revert_rolls_back() {
+        let mut host = InMemoryHost::new();
+        let it = run_at(&mut host, store_then(opcodes::OpCode::REVERT), 100_000);
+        assert!(host.accounts.get(&Address::from_low_u64_be(0xaa)).map_or(true, |a| a.storage.is_empty()));
+        assert_eq!(U256::from_big_endian(&it.context.return_data), U256::from(0x2a));
+        assert_eq!(host.get_refund(), 0);
+
+        let it = run_at(&mut host, store_then(opcodes::OpCode::RETURN), 100_000);
+        assert_eq!(U256::from_big_endian(&it.context.return_data), U256::from(0x2a));
+        assert!(!host.get_state(Address::from_low_u64_be(0xaa), H256::zero()).is_zero());
+    }
+
+    #[test]
+    fn test_interpreter_call_revert() {
+        let caller = Address::from_low_u64_be(0xaa);
+        let callee = Address::from_low_u64_be(0xbb);
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(caller).or_default().balance = U256::from(100);
+        host.accounts.entry(callee).or_default().code = store_then(opcodes::OpCode::REVERT);
+
+        let mut code = call_code(opcodes::OpCode::CALL, 0xbb, 10);
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 100_000);
+        // The revert payload reaches the caller, while the storage write and
+        // the value transfer are undone.
+        assert_eq!(output_words(&it), (U256::from(0x2a), U256::zero()));
+        assert_eq!(it.context.return_data.len(), 64);
+        assert!(host.get_state(callee, H256::zero()).is_zero());
+        assert_eq!(host.get_balance(caller), U256::from(100));
+        assert!(host.get_balance(callee).is_zero());
+        // The gas the callee did not use comes back, including what is left
+        // of the stipend.
+        let call = 24 + 3 + 2600 + 9000 + 15;
+        let callee_gas = 6 + 2100 + 20000 + 6 + 6 + 6;
+        assert_eq!(it.gas_used(), call + callee_gas - 2300);
+    }
+
+    #[test]
+    fn test_interpreter_snapshots_released() {
+        // Every frame drops its snapshot when it ends, whether it succeeds
+        // or not.
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xaa)).or_default().balance = U256::from(100);
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code =
+            store_then(opcodes::OpCode::RETURN);
+        host.accounts.entry(Address::from_low_u64_be(0xcc)).or_default().code =
+            store_then(opcodes::OpCode::REVERT);
+        let mut code = call_code(opcodes::OpCode::CALL, 0xbb, 1);
+        code.extend(call_code(opcodes::OpCode::CALL, 0xcc, 1));
+        code.extend(call_code(opcodes::OpCode::CALL, 0x04, 1));
+        code.extend(create_code(opcodes::OpCode::CREATE, &DEPLOY_FF));
+        run_at(&mut host, code, 1_000_000);
+        assert!(!host.get_state(Address::from_low_u64_be(0xbb), H256::zero()).is_zero());
+        assert!(host.snapshots.is_empty());
+
+        host.snapshot();
+        host.commit();
+        assert!(host.snapshots.is_empty());
+    }
+
+    #[test]
+    fn test_interpreter_negative_refund() {
+        let key = common::u256_to_h256(U256::one());
+        let mut host = InMemoryHost::new();
+        host.set_state(Address::zero(), key, common::u256_to_h256(U256::one()));
+        host.commit();
+
+        // Clear the slot in a frame, then roll back only its refund, as a
+        // host whose refund is journaled apart from storage may.
+        let snapshot = host.snapshot();
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x00,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+        context.contract.cgas = 100_000;
+        Interpreter::new(context).run(&mut host);
+        assert_eq!(host.get_refund(), 4800);
+        let cleared = host.accounts.clone();
+        host.revert_to_snapshot(snapshot);
+        host.accounts = cleared;
+        assert_eq!(host.get_refund(), 0);
+
+        // Restoring the slot takes back a refund that is no longer there.
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::PUSH1 as u8, 0x01,
+            opcodes::OpCode::SSTORE as u8,
+        ];
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(&mut host), ExecutionResult::Success(Vec::new(), 100_000 - 6 - 2100 - 100));
+        assert_eq!(host.get_refund(), 2900 - 100 - 4800);
+    }
+
+    #[test]
+    fn test_interpreter_call_halt_rolls_back() {
+        let callee = Address::from_low_u64_be(0xbb);
+        let mut host = InMemoryHost::new();
+        let mut code = store_then(opcodes::OpCode::RETURN);
+        code.pop();
+        code.push(opcodes::OpCode::INVALID as u8);
+        host.accounts.entry(callee).or_default().code = code;
+
+        let mut code = call_code(opcodes::OpCode::CALL, 0xbb, 0);
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 100_000);
+        assert_eq!(output_words(&it), (U256::zero(), U256::zero()));
+        assert!(host.get_state(callee, H256::zero()).is_zero());
+        assert_eq!(it.context.return_data.len(), 64);
+    }
+
+    #[test]
+    fn test_interpreter_revert_reason() {
+        // Reverts with Panic(0x12).
+        let mut context = core::EVMContext::new();
+        context.contract.code = vec![
+            opcodes::OpCode::PUSH4 as u8, 0x4e, 0x48, 0x7b, 0x71,
+            opcodes::OpCode::PUSH1 as u8, 224,
+            opcodes::OpCode::SHL as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 0x12,
+            opcodes::OpCode::PUSH1 as u8, 4,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 36,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::REVERT as u8,
+        ];
+        let mut it = Interpreter::new(context);
+        let r = it.run(&mut InMemoryHost::new());
+        assert_eq!(
+            r.revert_reason(&abi::ErrorAbi::default()),
+            Some(abi::RevertReason::Panic {
+                code: U256::from(0x12),
+                name: Some("division or modulo by zero"),
+            })
+        );
+        assert_eq!(ExecutionResult::Success(Vec::new(), 0).revert_reason(&abi::ErrorAbi::default()), None);
+    }
+
+    /// Code emitting LOG2 with topics 1 and 2 and the data 0x2a, then
+    /// ending with `last`.
+    fn log_then(last: opcodes::OpCode) -> Vec<u8> {
+        vec![
+            opcodes::OpCode::PUSH1 as u8, 0x2a,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::MSTORE as u8,
+            opcodes::OpCode::PUSH1 as u8, 2,
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 1,
+            opcodes::OpCode::PUSH1 as u8, 31,
+            opcodes::OpCode::LOG2 as u8,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            opcodes::OpCode::PUSH1 as u8, 0,
+            last as u8,
+        ]
+    }
+
+    #[test]
+    fn test_interpreter_logs() {
+        let caller = Address::from_low_u64_be(0xaa);
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xbb)).or_default().code =
+            log_then(opcodes::OpCode::RETURN);
+        host.accounts.entry(Address::from_low_u64_be(0xcc)).or_default().code =
+            log_then(opcodes::OpCode::REVERT);
+
+        // Log, call 0xbb which logs, call 0xcc which logs and reverts.
+        let mut code = log_then(opcodes::OpCode::JUMPDEST);
+        code.extend(call_code(opcodes::OpCode::CALL, 0xbb, 0));
+        code.extend(call_code(opcodes::OpCode::CALL, 0xcc, 0));
+        let it = run_at(&mut host, code, 1_000_000);
+
+        let log = |address: u64| Log {
+            address: Address::from_low_u64_be(address),
+            topics: vec![common::u256_to_h256(U256::one()), common::u256_to_h256(U256::from(2))],
+            data: vec![0x2a],
+        };
+        let expected = vec![log(0xaa), log(0xbb)];
+        assert_eq!(it.context.logs, expected);
+        assert_eq!(host.logs, expected);
+        assert_eq!(expected[0].address, caller);
+
+        // Nothing is kept when the outermost frame reverts.
+        let mut host = InMemoryHost::new();
+        let it = run_at(&mut host, log_then(opcodes::OpCode::REVERT), 1_000_000);
+        assert!(it.context.logs.is_empty());
+        assert!(host.logs.is_empty());
+    }
+
+    const SELFDESTRUCT_TO_BB: [u8; 3] = [opcodes::OpCode::PUSH1 as u8, 0xbb, opcodes::OpCode::SUICIDE as u8];
+
+    fn selfdestruct_with(fork: spec::Fork, host: &mut InMemoryHost) -> Interpreter {
+        let mut context = core::EVMContext::new();
+        context.conf = Rc::new(core::EVMConf::with_fork(fork));
+        context.contract.address = Address::from_low_u64_be(0xaa);
+        context.contract.code = SELFDESTRUCT_TO_BB.to_vec();
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        assert_eq!(it.run(host), ExecutionResult::Success(Vec::new(), it.context.contract.cgas));
+        it
+    }
+
+    #[test]
+    fn test_interpreter_selfdestruct() {
+        let contract = Address::from_low_u64_be(0xaa);
+        let beneficiary = Address::from_low_u64_be(0xbb);
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(contract).or_default().balance = U256::from(50);
+        let it = selfdestruct_with(spec::Fork::Berlin, &mut host);
+        // PUSH1, SELFDESTRUCT, a cold beneficiary and a new account.
+        // Before London up to half the gas used is refunded.
+        let used = 3 + 5000 + 2600 + 25000;
+        assert_eq!(it.gas_used(), used - used / 2);
+        assert_eq!(host.get_balance(beneficiary), U256::from(50));
+        assert_eq!(host.get_balance(contract), U256::zero());
+        assert!(host.has_suicided(contract));
+        assert_eq!(host.get_refund(), 24000);
+        host.commit();
+        assert!(!host.exists(contract));
+        assert!(host.exists(beneficiary));
+
+        // No refund from London on.
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(contract).or_default();
+        selfdestruct_with(spec::Fork::London, &mut host);
+        assert!(host.has_suicided(contract));
+        assert_eq!(host.get_refund(), 0);
+    }
+
+    #[test]
+    fn test_interpreter_selfdestruct_eip6780() {
+        let contract = Address::from_low_u64_be(0xaa);
+        let beneficiary = Address::from_low_u64_be(0xbb);
+
+        // An existing contract only loses its balance.
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(contract).or_default().balance = U256::from(50);
+        selfdestruct_with(spec::Fork::Cancun, &mut host);
+        assert_eq!(host.get_balance(beneficiary), U256::from(50));
+        assert!(!host.has_suicided(contract));
+        host.commit();
+        assert!(host.exists(contract));
+
+        // A contract created in the same transaction is deleted.
+        let mut host = InMemoryHost::new();
+        let it = run_at(&mut host, create_code(opcodes::OpCode::CREATE, &SELFDESTRUCT_TO_BB), 1_000_000);
+        let created = common::create_address(contract, U256::zero());
+        assert_eq!(
+            U256::from_big_endian(&it.context.return_data),
+            common::address_to_u256(created)
+        );
+        assert!(host.has_suicided(created));
+        host.commit();
+        assert!(!host.exists(created));
+    }
+
+    #[test]
+    fn test_interpreter_call_precompile() {
+        let mut host = InMemoryHost::new();
+        let mut code = call_code(opcodes::OpCode::STATICCALL, 0x02, 0);
+        return_output_and_flag(&mut code);
+        let it = run_at(&mut host, code, 100_000);
+        let expected = U256::from_big_endian(&[
+            0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f,
+            0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b,
+            0x78, 0x52, 0xb8, 0x55,
+        ]);
+        assert_eq!(output_words(&it), (expected, U256::one()));
+        // Precompiles are warm, and SHA-256 of nothing costs 60.
+        assert_eq!(it.gas_used(), 21 + 3 + 100 + 60 + 15);
+    }
+
+    /// Stores its caller in slot 0 and fails if called with value.
+    struct RecordCaller;
+
+    impl NativeContract for RecordCaller {
+        fn required_gas(&self, input: &[u8]) -> u64 {
+            100 + input.len() as u64
+        }
+
+        fn run(&self, call: &NativeCall, _input: &[u8], host: &mut dyn Host) -> Result<Vec<u8>, VmError> {
+            host.set_state(call.address, H256::zero(), common::u256_to_h256(common::address_to_u256(call.caller)));
+            if !call.value.is_zero() {
+                return Err(VmError::PrecompileFailure);
+            }
+            Ok(vec![0x2a; 32])
+        }
+    }
+
+    #[test]
+    fn test_interpreter_native_contracts() {
+        let native = Address::from_low_u64_be(0xcc);
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xaa)).or_default().balance = U256::one();
+        let run = |host: &mut InMemoryHost, code: Vec<u8>| {
+            let mut context = core::EVMContext::new();
+            Rc::make_mut(&mut context.conf).precompiles.register(native, Rc::new(RecordCaller));
+            context.contract.address = Address::from_low_u64_be(0xaa);
+            context.contract.code = code;
+            context.contract.cgas = 100_000;
+            let mut it = Interpreter::new(context);
+            it.run(host);
+            it
+        };
+
+        let mut code = call_code(opcodes::OpCode::CALL, 0xcc, 0);
+        return_output_and_flag(&mut code);
+        let it = run(&mut host, code);
+        assert_eq!(output_words(&it), (U256::from(&[0x2a; 32][..]), U256::one()));
+        assert_eq!(host.get_state(native, H256::zero()), common::u256_to_h256(U256::from(0xaa)));
+        // Native contracts are warm and charge their own gas.
+        assert_eq!(it.gas_used(), 24 + 3 + 100 + 100 + 15);
+
+        // A failing native contract has its changes rolled back.
+        let mut host = InMemoryHost::new();
+        host.accounts.entry(Address::from_low_u64_be(0xaa)).or_default().balance = U256::one();
+        let mut code = call_code(opcodes::OpCode::CALL, 0xcc, 1);
+        return_output_and_flag(&mut code);
+        let it = run(&mut host, code);
+        assert_eq!(output_words(&it).1, U256::zero());
+        assert!(host.get_state(native, H256::zero()).is_zero());
+        assert_eq!(host.get_balance(Address::from_low_u64_be(0xaa)), U256::one());
+    }
+
+    #[test]
+    fn test_interpreter_native_closure() {
+        // Under DELEGATECALL a native contract acts on the caller's state.
+        let mut host = InMemoryHost::new();
+        let mut context = core::EVMContext::new();
+        Rc::make_mut(&mut context.conf).precompiles.register_fn(
+            Address::from_low_u64_be(0xcc),
+            |_| 5,
+            |call, _, host| {
+                host.set_state(call.address, H256::zero(), common::u256_to_h256(U256::one()));
+                Ok(Vec::new())
+            },
+        );
+        context.contract.address = Address::from_low_u64_be(0xaa);
+        context.contract.code = call_code(opcodes::OpCode::DELEGATECALL, 0xcc, 0);
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        assert!(!host.get_state(Address::from_low_u64_be(0xaa), H256::zero()).is_zero());
+        assert!(host.get_state(Address::from_low_u64_be(0xcc), H256::zero()).is_zero());
+    }
+
+    #[test]
+    fn test_interpreter_native_static_violation() {
+        let native = Address::from_low_u64_be(0xcc);
+        let mut host = InMemoryHost::new();
+        let mut context = core::EVMContext::new();
+        Rc::make_mut(&mut context.conf).precompiles.register(native, Rc::new(RecordCaller));
+        context.contract.address = Address::from_low_u64_be(0xaa);
+        let mut code = call_code(opcodes::OpCode::STATICCALL, 0xcc, 0);
+        return_output_and_flag(&mut code);
+        context.contract.code = code;
+        context.contract.cgas = 100_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        assert_eq!(output_words(&it).1, U256::zero());
+        assert!(host.get_state(native, H256::zero()).is_zero());
+        assert!(host.accounts.get(&native).is_none());
+    }
+
+    #[test]
+    fn test_interpreter_native_logs() {
+        let mut host = InMemoryHost::new();
+        let mut context = core::EVMContext::new();
+        Rc::make_mut(&mut context.conf).precompiles.register_fn(
+            Address::from_low_u64_be(0xcc),
+            |_| 5,
+            |call, _, host| {
+                host.add_log(Log {
+                    address: call.address,
+                    topics: Vec::new(),
+                    data: vec![0x2b],
+                });
+                Ok(Vec::new())
+            },
+        );
+        context.contract.address = Address::from_low_u64_be(0xaa);
+        let mut code = log_then(opcodes::OpCode::JUMPDEST);
+        code.extend(call_code(opcodes::OpCode::CALL, 0xcc, 0));
+        context.contract.code = code;
+        context.contract.cgas = 1_000_000;
+        let mut it = Interpreter::new(context);
+        it.run(&mut host);
+        // The native log follows the one emitted before the call.
+        let data: Vec<Vec<u8>> = host.logs.iter().map(|l| l.data.clone()).collect();
+        assert_eq!(data, vec![vec![0x2a], vec![0x2b]]);
+        assert_eq!(it.context.logs, host.logs);
+    }
 }
@@ -6,3 +6,9 @@ pub mod opcodes;
 pub mod core;
 pub mod interpreter;
 pub mod common;
+pub mod err;
+pub mod gas;
+pub mod host;
+pub mod spec;
+pub mod abi;
+pub mod precompiled;
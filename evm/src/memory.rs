@@ -1,3 +1,11 @@
+use super::err::VmError;
+
+/// Memory a frame may use unless `EVMConf::max_memory` says otherwise.
+/// Paying for 32 MiB would take more than 2 billion gas.
+pub const DEFAULT_MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// The byte-addressed memory of a frame. It only ever grows, and always by
+/// whole 32-byte words.
 pub struct Memory {
     store: Vec<u8>,
 }
@@ -7,26 +15,31 @@ impl Memory {
         Memory { store: vec![] }
     }
 
-    pub fn set(&mut self, offset: usize, val: &[u8]) {
-        if offset + 32 > self.store.len() {
-            panic!("invalid memory: store empty")
+    /// Fails with `MemoryOutOfRange` unless the region was expanded first.
+    pub fn set(&mut self, offset: usize, val: &[u8]) -> Result<(), VmError> {
+        let end = offset.checked_add(val.len()).ok_or(VmError::MemoryOutOfRange)?;
+        if end > self.store.len() {
+            return Err(VmError::MemoryOutOfRange);
         }
 
-        self.store[offset..offset + val.len()].copy_from_slice(val)
+        self.store[offset..end].copy_from_slice(val);
+        Ok(())
     }
 
-    pub fn get(&self, offset: usize, size: usize) -> &[u8] {
-        &self.store[offset..offset + size]
+    /// Fails with `MemoryOutOfRange` unless the region was expanded first.
+    pub fn get(&self, offset: usize, size: usize) -> Result<&[u8], VmError> {
+        let end = offset.checked_add(size).ok_or(VmError::MemoryOutOfRange)?;
+        self.store.get(offset..end).ok_or(VmError::MemoryOutOfRange)
     }
 
-    pub fn resize(&mut self, size: usize) {
-        self.store.resize_default(size)
-    }
-
-    pub fn expand(&mut self, size:usize) {
+    /// Grows memory to hold at least `size` bytes, rounded up to a whole
+    /// number of words.
+    pub fn expand(&mut self, size: usize) -> Result<(), VmError> {
         if size > self.len() {
-            self.resize(size)
+            let words = size.checked_add(31).ok_or(VmError::MemoryOutOfRange)? / 32;
+            self.store.resize_default(words * 32)
         }
+        Ok(())
     }
 
     pub fn len(&self) -> usize {
@@ -38,38 +51,66 @@ impl Memory {
     }
 }
 
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
     #[test]
     fn test_memory_set_get() {
         let mut mem = Memory::new();
-        mem.resize(128);
+        mem.expand(128).unwrap();
         assert_eq!(mem.len(), 128);
-        let r = mem.get(8, 2);
+        let r = mem.get(8, 2).unwrap();
         assert_eq!(r[0], 0x00);
         assert_eq!(r[1], 0x00);
-        mem.set(8, &vec![0x01, 0x02]);
-        let r = mem.get(8, 2);
+        mem.set(8, &vec![0x01, 0x02]).unwrap();
+        let r = mem.get(8, 2).unwrap();
         assert_eq!(r[0], 0x01);
         assert_eq!(r[1], 0x02);
     }
 
     #[test]
-    fn test_memory_resize() {
+    fn test_memory_expand_keeps_data() {
         let mut mem = Memory::new();
-        mem.resize(128);
+        mem.expand(128).unwrap();
         let r: Vec<u8> = (0..128).map(|_| 0xFF).collect();
-        mem.set(0, &r);
+        mem.set(0, &r).unwrap();
 
-        mem.resize(256);
+        mem.expand(256).unwrap();
         assert_eq!(mem.len(), 256);
-        assert_eq!(mem.get(128, 1)[0], 0x00);
+        assert_eq!(mem.get(127, 1).unwrap()[0], 0xFF);
+        assert_eq!(mem.get(128, 1).unwrap()[0], 0x00);
+    }
+
+    #[test]
+    fn test_memory_set_out_of_range() {
+        let mut mem = Memory::new();
+        mem.expand(32).unwrap();
+        assert_eq!(mem.set(31, &[0x01, 0x02]), Err(VmError::MemoryOutOfRange));
+        assert_eq!(mem.set(31, &[0x01]), Ok(()));
+    }
 
-        mem.resize(64);
+    #[test]
+    fn test_memory_get_out_of_range() {
+        let mut mem = Memory::new();
+        mem.expand(32).unwrap();
+        assert_eq!(mem.get(0, 32).map(|r| r.len()), Ok(32));
+        assert_eq!(mem.get(1, 32), Err(VmError::MemoryOutOfRange));
+        assert_eq!(mem.get(usize::max_value(), 2), Err(VmError::MemoryOutOfRange));
+        assert_eq!(mem.set(usize::max_value(), &[0x01, 0x02]), Err(VmError::MemoryOutOfRange));
+    }
+
+    #[test]
+    fn test_memory_expand_by_words() {
+        let mut mem = Memory::new();
+        mem.expand(0).unwrap();
+        assert_eq!(mem.len(), 0);
+        mem.expand(1).unwrap();
+        assert_eq!(mem.len(), 32);
+        mem.expand(33).unwrap();
+        assert_eq!(mem.len(), 64);
+        // Memory never shrinks.
+        mem.expand(10).unwrap();
         assert_eq!(mem.len(), 64);
-        assert_eq!(mem.get(32, 1)[0], 0xFF);
+        assert_eq!(mem.expand(usize::max_value()), Err(VmError::MemoryOutOfRange));
     }
 }
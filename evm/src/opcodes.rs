@@ -1,7 +1,10 @@
-use std::convert::From;
+use super::err::VmError;
+use std::convert::TryFrom;
+use std::error;
 use std::fmt::{Display, Formatter, Result};
+use std::str::FromStr;
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum OpCode {
     STOP = 0x00,
     ADD = 0x01,
@@ -52,6 +55,9 @@ pub enum OpCode {
     NUMBER = 0x43,
     DIFFICULTY = 0x44,
     GASLIMIT = 0x45,
+    CHAINID = 0x46,
+    SELFBALANCE = 0x47,
+    BASEFEE = 0x48,
     POP = 0x50,
     MLOAD = 0x51,
     MSTORE = 0x52,
@@ -64,6 +70,7 @@ pub enum OpCode {
     MSIZE = 0x59,
     GAS = 0x5a,
     JUMPDEST = 0x5b,
+    PUSH0 = 0x5f,
     PUSH1 = 0x60,
     PUSH2 = 0x61,
     PUSH3 = 0x62,
@@ -141,158 +148,21 @@ pub enum OpCode {
     CREATE2 = 0xf5,
     REVERT = 0xfd,
     STATICCALL = 0xfa,
+    INVALID = 0xfe,
     SUICIDE = 0xff,
 }
 
 impl Display for OpCode {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        match *self {
-            OpCode::STOP => write!(f, "STOP"),
-            OpCode::ADD => write!(f, "ADD"),
-            OpCode::MUL => write!(f, "MUL"),
-            OpCode::SUB => write!(f, "SUB"),
-            OpCode::DIV => write!(f, "DIV"),
-            OpCode::SDIV => write!(f, "SDIV"),
-            OpCode::MOD => write!(f, "MOD"),
-            OpCode::SMOD => write!(f, "SMOD"),
-            OpCode::ADDMOD => write!(f, "ADDMOD"),
-            OpCode::MULMOD => write!(f, "MULMOD"),
-            OpCode::EXP => write!(f, "EXP"),
-            OpCode::SIGNEXTEND => write!(f, "SIGNEXTEND"),
-            OpCode::LT => write!(f, "LT"),
-            OpCode::GT => write!(f, "GT"),
-            OpCode::SLT => write!(f, "SLT"),
-            OpCode::SGT => write!(f, "SGT"),
-            OpCode::EQ => write!(f, "EQ"),
-            OpCode::ISZERO => write!(f, "ISZERO"),
-            OpCode::AND => write!(f, "AND"),
-            OpCode::OR => write!(f, "OR"),
-            OpCode::XOR => write!(f, "XOR"),
-            OpCode::NOT => write!(f, "NOT"),
-            OpCode::BYTE => write!(f, "BYTE"),
-            OpCode::SHL => write!(f, "SHL"),
-            OpCode::SHR => write!(f, "SHR"),
-            OpCode::SAR => write!(f, "SAR"),
-            OpCode::SHA3 => write!(f, "SHA3"),
-            OpCode::ADDRESS => write!(f, "ADDRESS"),
-            OpCode::BALANCE => write!(f, "BALANCE"),
-            OpCode::ORIGIN => write!(f, "ORIGIN"),
-            OpCode::CALLER => write!(f, "CALLER"),
-            OpCode::CALLVALUE => write!(f, "CALLVALUE"),
-            OpCode::CALLDATALOAD => write!(f, "CALLDATALOAD"),
-            OpCode::CALLDATASIZE => write!(f, "CALLDATASIZE"),
-            OpCode::CALLDATACOPY => write!(f, "CALLDATACOPY"),
-            OpCode::CODESIZE => write!(f, "CODESIZE"),
-            OpCode::CODECOPY => write!(f, "CODECOPY"),
-            OpCode::GASPRICE => write!(f, "GASPRICE"),
-            OpCode::EXTCODESIZE => write!(f, "EXTCODESIZE"),
-            OpCode::EXTCODECOPY => write!(f, "EXTCODECOPY"),
-            OpCode::RETURNDATASIZE => write!(f, "RETURNDATASIZE"),
-            OpCode::RETURNDATACOPY => write!(f, "RETURNDATACOPY"),
-            OpCode::EXTCODEHASH => write!(f, "EXTCODEHASH"),
-            OpCode::BLOCKHASH => write!(f, "BLOCKHASH"),
-            OpCode::COINBASE => write!(f, "COINBASE"),
-            OpCode::TIMESTAMP => write!(f, "TIMESTAMP"),
-            OpCode::NUMBER => write!(f, "NUMBER"),
-            OpCode::DIFFICULTY => write!(f, "DIFFICULTY"),
-            OpCode::GASLIMIT => write!(f, "GASLIMIT"),
-            OpCode::POP => write!(f, "POP"),
-            OpCode::MLOAD => write!(f, "MLOAD"),
-            OpCode::MSTORE => write!(f, "MSTORE"),
-            OpCode::MSTORE8 => write!(f, "MSTORE8"),
-            OpCode::SLOAD => write!(f, "SLOAD"),
-            OpCode::SSTORE => write!(f, "SSTORE"),
-            OpCode::JUMP => write!(f, "JUMP"),
-            OpCode::JUMPI => write!(f, "JUMPI"),
-            OpCode::PC => write!(f, "PC"),
-            OpCode::MSIZE => write!(f, "MSIZE"),
-            OpCode::GAS => write!(f, "GAS"),
-            OpCode::JUMPDEST => write!(f, "JUMPDEST"),
-            OpCode::PUSH1 => write!(f, "PUSH1"),
-            OpCode::PUSH2 => write!(f, "PUSH2"),
-            OpCode::PUSH3 => write!(f, "PUSH3"),
-            OpCode::PUSH4 => write!(f, "PUSH4"),
-            OpCode::PUSH5 => write!(f, "PUSH5"),
-            OpCode::PUSH6 => write!(f, "PUSH6"),
-            OpCode::PUSH7 => write!(f, "PUSH7"),
-            OpCode::PUSH8 => write!(f, "PUSH8"),
-            OpCode::PUSH9 => write!(f, "PUSH9"),
-            OpCode::PUSH10 => write!(f, "PUSH10"),
-            OpCode::PUSH11 => write!(f, "PUSH11"),
-            OpCode::PUSH12 => write!(f, "PUSH12"),
-            OpCode::PUSH13 => write!(f, "PUSH13"),
-            OpCode::PUSH14 => write!(f, "PUSH14"),
-            OpCode::PUSH15 => write!(f, "PUSH15"),
-            OpCode::PUSH16 => write!(f, "PUSH16"),
-            OpCode::PUSH17 => write!(f, "PUSH17"),
-            OpCode::PUSH18 => write!(f, "PUSH18"),
-            OpCode::PUSH19 => write!(f, "PUSH19"),
-            OpCode::PUSH20 => write!(f, "PUSH20"),
-            OpCode::PUSH21 => write!(f, "PUSH21"),
-            OpCode::PUSH22 => write!(f, "PUSH22"),
-            OpCode::PUSH23 => write!(f, "PUSH23"),
-            OpCode::PUSH24 => write!(f, "PUSH24"),
-            OpCode::PUSH25 => write!(f, "PUSH25"),
-            OpCode::PUSH26 => write!(f, "PUSH26"),
-            OpCode::PUSH27 => write!(f, "PUSH27"),
-            OpCode::PUSH28 => write!(f, "PUSH28"),
-            OpCode::PUSH29 => write!(f, "PUSH29"),
-            OpCode::PUSH30 => write!(f, "PUSH30"),
-            OpCode::PUSH31 => write!(f, "PUSH31"),
-            OpCode::PUSH32 => write!(f, "PUSH32"),
-            OpCode::DUP1 => write!(f, "DUP1"),
-            OpCode::DUP2 => write!(f, "DUP2"),
-            OpCode::DUP3 => write!(f, "DUP3"),
-            OpCode::DUP4 => write!(f, "DUP4"),
-            OpCode::DUP5 => write!(f, "DUP5"),
-            OpCode::DUP6 => write!(f, "DUP6"),
-            OpCode::DUP7 => write!(f, "DUP7"),
-            OpCode::DUP8 => write!(f, "DUP8"),
-            OpCode::DUP9 => write!(f, "DUP9"),
-            OpCode::DUP10 => write!(f, "DUP10"),
-            OpCode::DUP11 => write!(f, "DUP11"),
-            OpCode::DUP12 => write!(f, "DUP12"),
-            OpCode::DUP13 => write!(f, "DUP13"),
-            OpCode::DUP14 => write!(f, "DUP14"),
-            OpCode::DUP15 => write!(f, "DUP15"),
-            OpCode::DUP16 => write!(f, "DUP16"),
-            OpCode::SWAP1 => write!(f, "SWAP1"),
-            OpCode::SWAP2 => write!(f, "SWAP2"),
-            OpCode::SWAP3 => write!(f, "SWAP3"),
-            OpCode::SWAP4 => write!(f, "SWAP4"),
-            OpCode::SWAP5 => write!(f, "SWAP5"),
-            OpCode::SWAP6 => write!(f, "SWAP6"),
-            OpCode::SWAP7 => write!(f, "SWAP7"),
-            OpCode::SWAP8 => write!(f, "SWAP8"),
-            OpCode::SWAP9 => write!(f, "SWAP9"),
-            OpCode::SWAP10 => write!(f, "SWAP10"),
-            OpCode::SWAP11 => write!(f, "SWAP11"),
-            OpCode::SWAP12 => write!(f, "SWAP12"),
-            OpCode::SWAP13 => write!(f, "SWAP13"),
-            OpCode::SWAP14 => write!(f, "SWAP14"),
-            OpCode::SWAP15 => write!(f, "SWAP15"),
-            OpCode::SWAP16 => write!(f, "SWAP16"),
-            OpCode::LOG0 => write!(f, "LOG0"),
-            OpCode::LOG1 => write!(f, "LOG1"),
-            OpCode::LOG2 => write!(f, "LOG2"),
-            OpCode::LOG3 => write!(f, "LOG3"),
-            OpCode::LOG4 => write!(f, "LOG4"),
-            OpCode::CREATE => write!(f, "CREATE"),
-            OpCode::CALL => write!(f, "CALL"),
-            OpCode::CALLCODE => write!(f, "CALLCODE"),
-            OpCode::RETURN => write!(f, "RETURN"),
-            OpCode::DELEGATECALL => write!(f, "DELEGATECALL"),
-            OpCode::CREATE2 => write!(f, "CREATE2"),
-            OpCode::REVERT => write!(f, "REVERT"),
-            OpCode::STATICCALL => write!(f, "STATICCALL"),
-            OpCode::SUICIDE => write!(f, "SUICIDE"),
-        }
+        write!(f, "{}", self.info().name)
     }
 }
 
-impl From<u8> for OpCode {
-    fn from(n: u8) -> OpCode {
-        match n {
+impl TryFrom<u8> for OpCode {
+    type Error = VmError;
+
+    fn try_from(n: u8) -> std::result::Result<OpCode, VmError> {
+        Ok(match n {
             0x00 => OpCode::STOP,
             0x01 => OpCode::ADD,
             0x02 => OpCode::MUL,
@@ -342,6 +212,9 @@ impl From<u8> for OpCode {
             0x43 => OpCode::NUMBER,
             0x44 => OpCode::DIFFICULTY,
             0x45 => OpCode::GASLIMIT,
+            0x46 => OpCode::CHAINID,
+            0x47 => OpCode::SELFBALANCE,
+            0x48 => OpCode::BASEFEE,
             0x50 => OpCode::POP,
             0x51 => OpCode::MLOAD,
             0x52 => OpCode::MSTORE,
@@ -354,6 +227,7 @@ impl From<u8> for OpCode {
             0x59 => OpCode::MSIZE,
             0x5a => OpCode::GAS,
             0x5b => OpCode::JUMPDEST,
+            0x5f => OpCode::PUSH0,
             0x60 => OpCode::PUSH1,
             0x61 => OpCode::PUSH2,
             0x62 => OpCode::PUSH3,
@@ -431,15 +305,30 @@ impl From<u8> for OpCode {
             0xf5 => OpCode::CREATE2,
             0xfd => OpCode::REVERT,
             0xfa => OpCode::STATICCALL,
+            0xfe => OpCode::INVALID,
             0xff => OpCode::SUICIDE,
-            _ => panic!(""),
-        }
+            _ => return Err(VmError::InvalidOpcode(n)),
+        })
+    }
+}
+
+/// Error returned when parsing an unknown opcode mnemonic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOpCodeError(pub String);
+
+impl Display for ParseOpCodeError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "unknown opcode: {}", self.0)
     }
 }
 
-impl From<&str> for OpCode {
-    fn from(n: &str) -> OpCode {
-        match n {
+impl error::Error for ParseOpCodeError {}
+
+impl FromStr for OpCode {
+    type Err = ParseOpCodeError;
+
+    fn from_str(n: &str) -> std::result::Result<OpCode, ParseOpCodeError> {
+        Ok(match n {
             "STOP" => OpCode::STOP,
             "ADD" => OpCode::ADD,
             "MUL" => OpCode::MUL,
@@ -489,6 +378,9 @@ impl From<&str> for OpCode {
             "NUMBER" => OpCode::NUMBER,
             "DIFFICULTY" => OpCode::DIFFICULTY,
             "GASLIMIT" => OpCode::GASLIMIT,
+            "CHAINID" => OpCode::CHAINID,
+            "SELFBALANCE" => OpCode::SELFBALANCE,
+            "BASEFEE" => OpCode::BASEFEE,
             "POP" => OpCode::POP,
             "MLOAD" => OpCode::MLOAD,
             "MSTORE" => OpCode::MSTORE,
@@ -501,6 +393,7 @@ impl From<&str> for OpCode {
             "MSIZE" => OpCode::MSIZE,
             "GAS" => OpCode::GAS,
             "JUMPDEST" => OpCode::JUMPDEST,
+            "PUSH0" => OpCode::PUSH0,
             "PUSH1" => OpCode::PUSH1,
             "PUSH2" => OpCode::PUSH2,
             "PUSH3" => OpCode::PUSH3,
@@ -578,9 +471,10 @@ impl From<&str> for OpCode {
             "CREATE2" => OpCode::CREATE2,
             "REVERT" => OpCode::REVERT,
             "STATICCALL" => OpCode::STATICCALL,
+            "INVALID" => OpCode::INVALID,
             "SUICIDE" => OpCode::SUICIDE,
-            _ => panic!(""),
-        }
+            _ => return Err(ParseOpCodeError(n.to_string())),
+        })
     }
 }
 
@@ -664,6 +558,9 @@ impl OpCode {
             OpCode::NUMBER => GasPriceTier::Base,
             OpCode::DIFFICULTY => GasPriceTier::Base,
             OpCode::GASLIMIT => GasPriceTier::Base,
+            OpCode::CHAINID => GasPriceTier::Base,
+            OpCode::SELFBALANCE => GasPriceTier::Low,
+            OpCode::BASEFEE => GasPriceTier::Base,
             OpCode::POP => GasPriceTier::Base,
             OpCode::MLOAD => GasPriceTier::VeryLow,
             OpCode::MSTORE => GasPriceTier::VeryLow,
@@ -676,6 +573,7 @@ impl OpCode {
             OpCode::MSIZE => GasPriceTier::Base,
             OpCode::GAS => GasPriceTier::Base,
             OpCode::JUMPDEST => GasPriceTier::Special,
+            OpCode::PUSH0 => GasPriceTier::Base,
             OpCode::PUSH1 => GasPriceTier::VeryLow,
             OpCode::PUSH2 => GasPriceTier::VeryLow,
             OpCode::PUSH3 => GasPriceTier::VeryLow,
@@ -754,6 +652,362 @@ impl OpCode {
             OpCode::SUICIDE => GasPriceTier::Special,
             OpCode::CREATE2 => GasPriceTier::Special,
             OpCode::REVERT => GasPriceTier::Zero,
+            OpCode::INVALID => GasPriceTier::Zero,
+        }
+    }
+}
+
+/// Static properties of an instruction, shared by the interpreter and by
+/// tools that walk bytecode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpInfo {
+    pub name: &'static str,
+    /// Number of items popped from the stack.
+    pub inputs: usize,
+    /// Number of items pushed to the stack.
+    pub outputs: usize,
+    /// Number of immediate bytes following the opcode in code.
+    pub immediate: usize,
+    /// Whether the instruction ends a basic block.
+    pub terminator: bool,
+    /// Whether the instruction always modifies state, which is forbidden in a
+    /// static call. CALL only does so when it transfers value, so it is
+    /// checked separately.
+    pub writes_state: bool,
+}
+
+const fn info(
+    name: &'static str,
+    inputs: usize,
+    outputs: usize,
+    immediate: usize,
+    terminator: bool,
+    writes_state: bool,
+) -> Option<OpInfo> {
+    Some(OpInfo {
+        name,
+        inputs,
+        outputs,
+        immediate,
+        terminator,
+        writes_state,
+    })
+}
+
+#[rustfmt::skip]
+static OP_INFO: [Option<OpInfo>; 256] = [
+    /* 0x00 */ info("STOP", 0, 0, 0, true, false),
+    /* 0x01 */ info("ADD", 2, 1, 0, false, false),
+    /* 0x02 */ info("MUL", 2, 1, 0, false, false),
+    /* 0x03 */ info("SUB", 2, 1, 0, false, false),
+    /* 0x04 */ info("DIV", 2, 1, 0, false, false),
+    /* 0x05 */ info("SDIV", 2, 1, 0, false, false),
+    /* 0x06 */ info("MOD", 2, 1, 0, false, false),
+    /* 0x07 */ info("SMOD", 2, 1, 0, false, false),
+    /* 0x08 */ info("ADDMOD", 3, 1, 0, false, false),
+    /* 0x09 */ info("MULMOD", 3, 1, 0, false, false),
+    /* 0x0a */ info("EXP", 2, 1, 0, false, false),
+    /* 0x0b */ info("SIGNEXTEND", 2, 1, 0, false, false),
+    /* 0x0c */ None,
+    /* 0x0d */ None,
+    /* 0x0e */ None,
+    /* 0x0f */ None,
+    /* 0x10 */ info("LT", 2, 1, 0, false, false),
+    /* 0x11 */ info("GT", 2, 1, 0, false, false),
+    /* 0x12 */ info("SLT", 2, 1, 0, false, false),
+    /* 0x13 */ info("SGT", 2, 1, 0, false, false),
+    /* 0x14 */ info("EQ", 2, 1, 0, false, false),
+    /* 0x15 */ info("ISZERO", 1, 1, 0, false, false),
+    /* 0x16 */ info("AND", 2, 1, 0, false, false),
+    /* 0x17 */ info("OR", 2, 1, 0, false, false),
+    /* 0x18 */ info("XOR", 2, 1, 0, false, false),
+    /* 0x19 */ info("NOT", 1, 1, 0, false, false),
+    /* 0x1a */ info("BYTE", 2, 1, 0, false, false),
+    /* 0x1b */ info("SHL", 2, 1, 0, false, false),
+    /* 0x1c */ info("SHR", 2, 1, 0, false, false),
+    /* 0x1d */ info("SAR", 2, 1, 0, false, false),
+    /* 0x1e */ None,
+    /* 0x1f */ None,
+    /* 0x20 */ info("SHA3", 2, 1, 0, false, false),
+    /* 0x21 */ None,
+    /* 0x22 */ None,
+    /* 0x23 */ None,
+    /* 0x24 */ None,
+    /* 0x25 */ None,
+    /* 0x26 */ None,
+    /* 0x27 */ None,
+    /* 0x28 */ None,
+    /* 0x29 */ None,
+    /* 0x2a */ None,
+    /* 0x2b */ None,
+    /* 0x2c */ None,
+    /* 0x2d */ None,
+    /* 0x2e */ None,
+    /* 0x2f */ None,
+    /* 0x30 */ info("ADDRESS", 0, 1, 0, false, false),
+    /* 0x31 */ info("BALANCE", 1, 1, 0, false, false),
+    /* 0x32 */ info("ORIGIN", 0, 1, 0, false, false),
+    /* 0x33 */ info("CALLER", 0, 1, 0, false, false),
+    /* 0x34 */ info("CALLVALUE", 0, 1, 0, false, false),
+    /* 0x35 */ info("CALLDATALOAD", 1, 1, 0, false, false),
+    /* 0x36 */ info("CALLDATASIZE", 0, 1, 0, false, false),
+    /* 0x37 */ info("CALLDATACOPY", 3, 0, 0, false, false),
+    /* 0x38 */ info("CODESIZE", 0, 1, 0, false, false),
+    /* 0x39 */ info("CODECOPY", 3, 0, 0, false, false),
+    /* 0x3a */ info("GASPRICE", 0, 1, 0, false, false),
+    /* 0x3b */ info("EXTCODESIZE", 1, 1, 0, false, false),
+    /* 0x3c */ info("EXTCODECOPY", 4, 0, 0, false, false),
+    /* 0x3d */ info("RETURNDATASIZE", 0, 1, 0, false, false),
+    /* 0x3e */ info("RETURNDATACOPY", 3, 0, 0, false, false),
+    /* 0x3f */ info("EXTCODEHASH", 1, 1, 0, false, false),
+    /* 0x40 */ info("BLOCKHASH", 1, 1, 0, false, false),
+    /* 0x41 */ info("COINBASE", 0, 1, 0, false, false),
+    /* 0x42 */ info("TIMESTAMP", 0, 1, 0, false, false),
+    /* 0x43 */ info("NUMBER", 0, 1, 0, false, false),
+    /* 0x44 */ info("DIFFICULTY", 0, 1, 0, false, false),
+    /* 0x45 */ info("GASLIMIT", 0, 1, 0, false, false),
+    /* 0x46 */ info("CHAINID", 0, 1, 0, false, false),
+    /* 0x47 */ info("SELFBALANCE", 0, 1, 0, false, false),
+    /* 0x48 */ info("BASEFEE", 0, 1, 0, false, false),
+    /* 0x49 */ None,
+    /* 0x4a */ None,
+    /* 0x4b */ None,
+    /* 0x4c */ None,
+    /* 0x4d */ None,
+    /* 0x4e */ None,
+    /* 0x4f */ None,
+    /* 0x50 */ info("POP", 1, 0, 0, false, false),
+    /* 0x51 */ info("MLOAD", 1, 1, 0, false, false),
+    /* 0x52 */ info("MSTORE", 2, 0, 0, false, false),
+    /* 0x53 */ info("MSTORE8", 2, 0, 0, false, false),
+    /* 0x54 */ info("SLOAD", 1, 1, 0, false, false),
+    /* 0x55 */ info("SSTORE", 2, 0, 0, false, true),
+    /* 0x56 */ info("JUMP", 1, 0, 0, true, false),
+    /* 0x57 */ info("JUMPI", 2, 0, 0, false, false),
+    /* 0x58 */ info("PC", 0, 1, 0, false, false),
+    /* 0x59 */ info("MSIZE", 0, 1, 0, false, false),
+    /* 0x5a */ info("GAS", 0, 1, 0, false, false),
+    /* 0x5b */ info("JUMPDEST", 0, 0, 0, false, false),
+    /* 0x5c */ None,
+    /* 0x5d */ None,
+    /* 0x5e */ None,
+    /* 0x5f */ info("PUSH0", 0, 1, 0, false, false),
+    /* 0x60 */ info("PUSH1", 0, 1, 1, false, false),
+    /* 0x61 */ info("PUSH2", 0, 1, 2, false, false),
+    /* 0x62 */ info("PUSH3", 0, 1, 3, false, false),
+    /* 0x63 */ info("PUSH4", 0, 1, 4, false, false),
+    /* 0x64 */ info("PUSH5", 0, 1, 5, false, false),
+    /* 0x65 */ info("PUSH6", 0, 1, 6, false, false),
+    /* 0x66 */ info("PUSH7", 0, 1, 7, false, false),
+    /* 0x67 */ info("PUSH8", 0, 1, 8, false, false),
+    /* 0x68 */ info("PUSH9", 0, 1, 9, false, false),
+    /* 0x69 */ info("PUSH10", 0, 1, 10, false, false),
+    /* 0x6a */ info("PUSH11", 0, 1, 11, false, false),
+    /* 0x6b */ info("PUSH12", 0, 1, 12, false, false),
+    /* 0x6c */ info("PUSH13", 0, 1, 13, false, false),
+    /* 0x6d */ info("PUSH14", 0, 1, 14, false, false),
+    /* 0x6e */ info("PUSH15", 0, 1, 15, false, false),
+    /* 0x6f */ info("PUSH16", 0, 1, 16, false, false),
+    /* 0x70 */ info("PUSH17", 0, 1, 17, false, false),
+    /* 0x71 */ info("PUSH18", 0, 1, 18, false, false),
+    /* 0x72 */ info("PUSH19", 0, 1, 19, false, false),
+    /* 0x73 */ info("PUSH20", 0, 1, 20, false, false),
+    /* 0x74 */ info("PUSH21", 0, 1, 21, false, false),
+    /* 0x75 */ info("PUSH22", 0, 1, 22, false, false),
+    /* 0x76 */ info("PUSH23", 0, 1, 23, false, false),
+    /* 0x77 */ info("PUSH24", 0, 1, 24, false, false),
+    /* 0x78 */ info("PUSH25", 0, 1, 25, false, false),
+    /* 0x79 */ info("PUSH26", 0, 1, 26, false, false),
+    /* 0x7a */ info("PUSH27", 0, 1, 27, false, false),
+    /* 0x7b */ info("PUSH28", 0, 1, 28, false, false),
+    /* 0x7c */ info("PUSH29", 0, 1, 29, false, false),
+    /* 0x7d */ info("PUSH30", 0, 1, 30, false, false),
+    /* 0x7e */ info("PUSH31", 0, 1, 31, false, false),
+    /* 0x7f */ info("PUSH32", 0, 1, 32, false, false),
+    /* 0x80 */ info("DUP1", 1, 2, 0, false, false),
+    /* 0x81 */ info("DUP2", 2, 3, 0, false, false),
+    /* 0x82 */ info("DUP3", 3, 4, 0, false, false),
+    /* 0x83 */ info("DUP4", 4, 5, 0, false, false),
+    /* 0x84 */ info("DUP5", 5, 6, 0, false, false),
+    /* 0x85 */ info("DUP6", 6, 7, 0, false, false),
+    /* 0x86 */ info("DUP7", 7, 8, 0, false, false),
+    /* 0x87 */ info("DUP8", 8, 9, 0, false, false),
+    /* 0x88 */ info("DUP9", 9, 10, 0, false, false),
+    /* 0x89 */ info("DUP10", 10, 11, 0, false, false),
+    /* 0x8a */ info("DUP11", 11, 12, 0, false, false),
+    /* 0x8b */ info("DUP12", 12, 13, 0, false, false),
+    /* 0x8c */ info("DUP13", 13, 14, 0, false, false),
+    /* 0x8d */ info("DUP14", 14, 15, 0, false, false),
+    /* 0x8e */ info("DUP15", 15, 16, 0, false, false),
+    /* 0x8f */ info("DUP16", 16, 17, 0, false, false),
+    /* 0x90 */ info("SWAP1", 2, 2, 0, false, false),
+    /* 0x91 */ info("SWAP2", 3, 3, 0, false, false),
+    /* 0x92 */ info("SWAP3", 4, 4, 0, false, false),
+    /* 0x93 */ info("SWAP4", 5, 5, 0, false, false),
+    /* 0x94 */ info("SWAP5", 6, 6, 0, false, false),
+    /* 0x95 */ info("SWAP6", 7, 7, 0, false, false),
+    /* 0x96 */ info("SWAP7", 8, 8, 0, false, false),
+    /* 0x97 */ info("SWAP8", 9, 9, 0, false, false),
+    /* 0x98 */ info("SWAP9", 10, 10, 0, false, false),
+    /* 0x99 */ info("SWAP10", 11, 11, 0, false, false),
+    /* 0x9a */ info("SWAP11", 12, 12, 0, false, false),
+    /* 0x9b */ info("SWAP12", 13, 13, 0, false, false),
+    /* 0x9c */ info("SWAP13", 14, 14, 0, false, false),
+    /* 0x9d */ info("SWAP14", 15, 15, 0, false, false),
+    /* 0x9e */ info("SWAP15", 16, 16, 0, false, false),
+    /* 0x9f */ info("SWAP16", 17, 17, 0, false, false),
+    /* 0xa0 */ info("LOG0", 2, 0, 0, false, true),
+    /* 0xa1 */ info("LOG1", 3, 0, 0, false, true),
+    /* 0xa2 */ info("LOG2", 4, 0, 0, false, true),
+    /* 0xa3 */ info("LOG3", 5, 0, 0, false, true),
+    /* 0xa4 */ info("LOG4", 6, 0, 0, false, true),
+    /* 0xa5 */ None,
+    /* 0xa6 */ None,
+    /* 0xa7 */ None,
+    /* 0xa8 */ None,
+    /* 0xa9 */ None,
+    /* 0xaa */ None,
+    /* 0xab */ None,
+    /* 0xac */ None,
+    /* 0xad */ None,
+    /* 0xae */ None,
+    /* 0xaf */ None,
+    /* 0xb0 */ None,
+    /* 0xb1 */ None,
+    /* 0xb2 */ None,
+    /* 0xb3 */ None,
+    /* 0xb4 */ None,
+    /* 0xb5 */ None,
+    /* 0xb6 */ None,
+    /* 0xb7 */ None,
+    /* 0xb8 */ None,
+    /* 0xb9 */ None,
+    /* 0xba */ None,
+    /* 0xbb */ None,
+    /* 0xbc */ None,
+    /* 0xbd */ None,
+    /* 0xbe */ None,
+    /* 0xbf */ None,
+    /* 0xc0 */ None,
+    /* 0xc1 */ None,
+    /* 0xc2 */ None,
+    /* 0xc3 */ None,
+    /* 0xc4 */ None,
+    /* 0xc5 */ None,
+    /* 0xc6 */ None,
+    /* 0xc7 */ None,
+    /* 0xc8 */ None,
+    /* 0xc9 */ None,
+    /* 0xca */ None,
+    /* 0xcb */ None,
+    /* 0xcc */ None,
+    /* 0xcd */ None,
+    /* 0xce */ None,
+    /* 0xcf */ None,
+    /* 0xd0 */ None,
+    /* 0xd1 */ None,
+    /* 0xd2 */ None,
+    /* 0xd3 */ None,
+    /* 0xd4 */ None,
+    /* 0xd5 */ None,
+    /* 0xd6 */ None,
+    /* 0xd7 */ None,
+    /* 0xd8 */ None,
+    /* 0xd9 */ None,
+    /* 0xda */ None,
+    /* 0xdb */ None,
+    /* 0xdc */ None,
+    /* 0xdd */ None,
+    /* 0xde */ None,
+    /* 0xdf */ None,
+    /* 0xe0 */ None,
+    /* 0xe1 */ None,
+    /* 0xe2 */ None,
+    /* 0xe3 */ None,
+    /* 0xe4 */ None,
+    /* 0xe5 */ None,
+    /* 0xe6 */ None,
+    /* 0xe7 */ None,
+    /* 0xe8 */ None,
+    /* 0xe9 */ None,
+    /* 0xea */ None,
+    /* 0xeb */ None,
+    /* 0xec */ None,
+    /* 0xed */ None,
+    /* 0xee */ None,
+    /* 0xef */ None,
+    /* 0xf0 */ info("CREATE", 3, 1, 0, false, true),
+    /* 0xf1 */ info("CALL", 7, 1, 0, false, false),
+    /* 0xf2 */ info("CALLCODE", 7, 1, 0, false, false),
+    /* 0xf3 */ info("RETURN", 2, 0, 0, true, false),
+    /* 0xf4 */ info("DELEGATECALL", 6, 1, 0, false, false),
+    /* 0xf5 */ info("CREATE2", 4, 1, 0, false, true),
+    /* 0xf6 */ None,
+    /* 0xf7 */ None,
+    /* 0xf8 */ None,
+    /* 0xf9 */ None,
+    /* 0xfa */ info("STATICCALL", 6, 1, 0, false, false),
+    /* 0xfb */ None,
+    /* 0xfc */ None,
+    /* 0xfd */ info("REVERT", 2, 0, 0, true, false),
+    /* 0xfe */ info("INVALID", 0, 0, 0, true, false),
+    /* 0xff */ info("SUICIDE", 1, 0, 0, true, true),
+];
+
+/// Metadata of the instruction encoded by `byte`, if it is defined.
+pub fn op_info(byte: u8) -> Option<&'static OpInfo> {
+    OP_INFO[byte as usize].as_ref()
+}
+
+impl OpCode {
+    pub fn info(&self) -> &'static OpInfo {
+        // Every variant has an entry in the table.
+        OP_INFO[*self as usize].as_ref().unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_opcode_try_from_u8() {
+        for n in 0..=255u8 {
+            if let Ok(op) = OpCode::try_from(n) {
+                assert_eq!(op as u8, n);
+                assert_eq!(OpCode::from_str(&op.to_string()), Ok(op));
+            }
+        }
+        assert_eq!(OpCode::try_from(0x00), Ok(OpCode::STOP));
+        assert_eq!(OpCode::try_from(0xfe), Ok(OpCode::INVALID));
+        assert_eq!(OpCode::try_from(0x0c), Err(VmError::InvalidOpcode(0x0c)));
+        assert_eq!(OpCode::try_from(0x21), Err(VmError::InvalidOpcode(0x21)));
+    }
+
+    #[test]
+    fn test_opcode_info() {
+        for n in 0..=255u8 {
+            assert_eq!(OpCode::try_from(n).is_ok(), op_info(n).is_some());
         }
+        let push32 = OpCode::PUSH32.info();
+        assert_eq!(push32.immediate, 32);
+        assert_eq!((push32.inputs, push32.outputs), (0, 1));
+        assert_eq!((OpCode::SWAP16.info().inputs, OpCode::SWAP16.info().outputs), (17, 17));
+        assert_eq!((OpCode::DUP16.info().inputs, OpCode::DUP16.info().outputs), (16, 17));
+        assert_eq!((OpCode::LOG4.info().inputs, OpCode::LOG4.info().outputs), (6, 0));
+        assert!(OpCode::JUMP.info().terminator);
+        assert!(!OpCode::JUMPI.info().terminator);
+        assert!(OpCode::SSTORE.info().writes_state);
+        assert!(!OpCode::CALL.info().writes_state);
+        assert_eq!(OpCode::CALLDATACOPY.to_string(), "CALLDATACOPY");
+    }
+
+    #[test]
+    fn test_opcode_from_str() {
+        assert_eq!("PUSH1".parse::<OpCode>(), Ok(OpCode::PUSH1));
+        assert_eq!(
+            "PUSH33".parse::<OpCode>(),
+            Err(ParseOpCodeError("PUSH33".to_string()))
+        );
     }
 }
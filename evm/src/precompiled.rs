@@ -0,0 +1,991 @@
+//! Precompiled contracts. The call opcodes run these natively instead of
+//! executing code when they call one of the registered addresses. Besides
+//! the standard precompiles, chains can register their own native contracts
+//! with access to the state.
+use super::common;
+use super::err::VmError;
+use super::host::{BlockEnv, Host, Log, TxEnv};
+use super::spec::Fork;
+use ethereum_types::*;
+use num_bigint::BigUint;
+use ripemd160::Ripemd160;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::rc::Rc;
+use substrate_bn::{self as bn, Group};
+
+pub trait Precompile {
+    /// Gas charged for running on `input`.
+    fn required_gas(&self, input: &[u8]) -> u64;
+    /// Runs on `input`. An error fails the call and consumes all gas given
+    /// to it.
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError>;
+}
+
+/// The frame a native contract is called in.
+pub struct NativeCall {
+    pub caller: Address,
+    /// Address whose state the call acts on. Differs from the contract's
+    /// own address under CALLCODE and DELEGATECALL.
+    pub address: Address,
+    pub value: U256,
+    /// Set under STATICCALL. Any change the contract attempts through its
+    /// host is dropped and fails the call with `StaticViolation`.
+    pub is_static: bool,
+}
+
+/// A contract implemented in Rust, such as a chain's system contracts.
+/// Changes it makes through `host` are rolled back if it returns an error.
+pub trait NativeContract {
+    fn required_gas(&self, input: &[u8]) -> u64;
+    fn run(&self, call: &NativeCall, input: &[u8], host: &mut dyn Host) -> Result<Vec<u8>, VmError>;
+}
+
+/// A native contract made of a gas function and a closure.
+pub struct NativeFn<G, R> {
+    gas: G,
+    run: R,
+}
+
+impl<G, R> NativeContract for NativeFn<G, R>
+where
+    G: Fn(&[u8]) -> u64,
+    R: Fn(&NativeCall, &[u8], &mut dyn Host) -> Result<Vec<u8>, VmError>,
+{
+    fn required_gas(&self, input: &[u8]) -> u64 {
+        (self.gas)(input)
+    }
+
+    fn run(&self, call: &NativeCall, input: &[u8], host: &mut dyn Host) -> Result<Vec<u8>, VmError> {
+        (self.run)(call, input, host)
+    }
+}
+
+#[derive(Clone)]
+enum Entry {
+    Builtin(Rc<dyn Precompile>),
+    Native(Rc<dyn NativeContract>),
+}
+
+/// Precompiles and native contracts by address.
+#[derive(Clone, Default)]
+pub struct Precompiles {
+    contracts: HashMap<Address, Entry>,
+}
+
+impl Precompiles {
+    pub fn new() -> Self {
+        Precompiles::default()
+    }
+
+    /// The precompiles defined under `fork`.
+    pub fn with_fork(fork: Fork) -> Self {
+        let mut precompiles = Precompiles::new();
+        precompiles.insert(Address::from_low_u64_be(1), Rc::new(EcRecover));
+        precompiles.insert(Address::from_low_u64_be(2), Rc::new(Sha256Hash));
+        precompiles.insert(Address::from_low_u64_be(3), Rc::new(Ripemd160Hash));
+        precompiles.insert(Address::from_low_u64_be(4), Rc::new(Identity));
+        if fork >= Fork::Byzantium {
+            let modexp = ModExp {
+                eip2565: fork >= Fork::Berlin,
+            };
+            precompiles.insert(Address::from_low_u64_be(5), Rc::new(modexp));
+            let istanbul = fork >= Fork::Istanbul;
+            let add = Bn128Add {
+                gas: if istanbul { 150 } else { 500 },
+            };
+            let mul = Bn128Mul {
+                gas: if istanbul { 6000 } else { 40000 },
+            };
+            let pairing = Bn128Pairing {
+                gas_base: if istanbul { 45000 } else { 100_000 },
+                gas_pair: if istanbul { 34000 } else { 80000 },
+            };
+            precompiles.insert(Address::from_low_u64_be(6), Rc::new(add));
+            precompiles.insert(Address::from_low_u64_be(7), Rc::new(mul));
+            precompiles.insert(Address::from_low_u64_be(8), Rc::new(pairing));
+        }
+        if fork >= Fork::Istanbul {
+            precompiles.insert(Address::from_low_u64_be(9), Rc::new(Blake2F));
+        }
+        precompiles
+    }
+
+    pub fn insert(&mut self, address: Address, contract: Rc<dyn Precompile>) {
+        self.contracts.insert(address, Entry::Builtin(contract));
+    }
+
+    /// Registers a native contract at `address`, replacing anything there.
+    pub fn register(&mut self, address: Address, contract: Rc<dyn NativeContract>) {
+        self.contracts.insert(address, Entry::Native(contract));
+    }
+
+    /// Registers a native contract given as a gas function and a closure.
+    pub fn register_fn<G, R>(&mut self, address: Address, gas: G, run: R)
+    where
+        G: Fn(&[u8]) -> u64 + 'static,
+        R: Fn(&NativeCall, &[u8], &mut dyn Host) -> Result<Vec<u8>, VmError> + 'static,
+    {
+        self.register(address, Rc::new(NativeFn { gas, run }));
+    }
+
+    pub fn remove(&mut self, address: &Address) {
+        self.contracts.remove(address);
+    }
+
+    pub fn contains(&self, address: &Address) -> bool {
+        self.contracts.contains_key(address)
+    }
+
+    /// Gas charged for calling `address` with `input`.
+    pub fn required_gas(&self, address: &Address, input: &[u8]) -> u64 {
+        match self.contracts.get(address) {
+            Some(Entry::Builtin(contract)) => contract.required_gas(input),
+            Some(Entry::Native(contract)) => contract.required_gas(input),
+            None => u64::max_value(),
+        }
+    }
+
+    /// Runs the contract at `address`. Logs emitted by a native contract are
+    /// appended to `logs` rather than handed to `host`, so the caller can
+    /// keep them in order with its own.
+    pub fn run(
+        &self,
+        address: &Address,
+        call: &NativeCall,
+        input: &[u8],
+        host: &mut dyn Host,
+        logs: &mut Vec<Log>,
+    ) -> Result<Vec<u8>, VmError> {
+        match self.contracts.get(address) {
+            Some(Entry::Builtin(contract)) => contract.run(input),
+            Some(Entry::Native(contract)) => {
+                let mut native = NativeHost {
+                    host,
+                    is_static: call.is_static,
+                    violation: false,
+                    logs: Vec::new(),
+                    log_marks: Vec::new(),
+                };
+                let output = contract.run(call, input, &mut native);
+                if native.violation {
+                    return Err(VmError::StaticViolation);
+                }
+                let output = output?;
+                logs.append(&mut native.logs);
+                Ok(output)
+            }
+            None => Err(VmError::PrecompileFailure),
+        }
+    }
+}
+
+/// The host seen by a native contract. It refuses changes under STATICCALL
+/// and buffers logs for the calling frame.
+struct NativeHost<'a> {
+    host: &'a mut dyn Host,
+    is_static: bool,
+    /// Set once the contract attempts a change under STATICCALL.
+    violation: bool,
+    logs: Vec<Log>,
+    /// Number of buffered logs at each snapshot the contract took.
+    log_marks: Vec<(usize, usize)>,
+}
+
+impl<'a> NativeHost<'a> {
+    /// Whether a change may go through, recording a violation if not.
+    fn writable(&mut self) -> bool {
+        if self.is_static {
+            self.violation = true;
+        }
+        !self.is_static
+    }
+}
+
+impl<'a> Host for NativeHost<'a> {
+    fn get_balance(&self, address: Address) -> U256 {
+        self.host.get_balance(address)
+    }
+
+    fn get_code(&self, address: Address) -> Vec<u8> {
+        self.host.get_code(address)
+    }
+
+    fn get_code_hash(&self, address: Address) -> H256 {
+        self.host.get_code_hash(address)
+    }
+
+    fn get_code_size(&self, address: Address) -> usize {
+        self.host.get_code_size(address)
+    }
+
+    fn set_code(&mut self, address: Address, code: &[u8]) {
+        if self.writable() {
+            self.host.set_code(address, code)
+        }
+    }
+
+    fn get_nonce(&self, address: Address) -> Result<U256, VmError> {
+        self.host.get_nonce(address)
+    }
+
+    fn set_nonce(&mut self, address: Address, nonce: U256) {
+        if self.writable() {
+            self.host.set_nonce(address, nonce)
+        }
+    }
+
+    fn create_account(&mut self, address: Address) {
+        if self.writable() {
+            self.host.create_account(address)
+        }
+    }
+
+    fn suicide(&mut self, address: Address) -> bool {
+        self.writable() && self.host.suicide(address)
+    }
+
+    fn has_suicided(&self, address: Address) -> bool {
+        self.host.has_suicided(address)
+    }
+
+    fn add_balance(&mut self, address: Address, value: U256) {
+        if self.writable() {
+            self.host.add_balance(address, value)
+        }
+    }
+
+    fn sub_balance(&mut self, address: Address, value: U256) {
+        if self.writable() {
+            self.host.sub_balance(address, value)
+        }
+    }
+
+    fn exists(&self, address: Address) -> bool {
+        self.host.exists(address)
+    }
+
+    fn empty(&self, address: Address) -> bool {
+        self.host.empty(address)
+    }
+
+    fn get_state(&self, address: Address, key: H256) -> H256 {
+        self.host.get_state(address, key)
+    }
+
+    fn set_state(&mut self, address: Address, key: H256, value: H256) {
+        if self.writable() {
+            self.host.set_state(address, key, value)
+        }
+    }
+
+    fn get_committed_state(&self, address: Address, key: H256) -> H256 {
+        self.host.get_committed_state(address, key)
+    }
+
+    fn add_refund(&mut self, gas: u64) {
+        if self.writable() {
+            self.host.add_refund(gas)
+        }
+    }
+
+    fn sub_refund(&mut self, gas: u64) {
+        if self.writable() {
+            self.host.sub_refund(gas)
+        }
+    }
+
+    fn get_refund(&self) -> i64 {
+        self.host.get_refund()
+    }
+
+    fn get_block_hash(&self, number: U256) -> H256 {
+        self.host.get_block_hash(number)
+    }
+
+    fn add_log(&mut self, log: Log) {
+        if self.writable() {
+            self.logs.push(log)
+        }
+    }
+
+    fn snapshot(&mut self) -> usize {
+        let id = self.host.snapshot();
+        self.log_marks.push((id, self.logs.len()));
+        id
+    }
+
+    fn revert_to_snapshot(&mut self, id: usize) {
+        self.host.revert_to_snapshot(id);
+        if let Some(i) = self.log_marks.iter().position(|(mark, _)| *mark == id) {
+            self.logs.truncate(self.log_marks[i].1);
+            self.log_marks.truncate(i);
+        }
+    }
+
+    fn discard_snapshot(&mut self, id: usize) {
+        self.host.discard_snapshot(id);
+        if let Some(i) = self.log_marks.iter().position(|(mark, _)| *mark == id) {
+            self.log_marks.truncate(i);
+        }
+    }
+
+    fn block(&self) -> &BlockEnv {
+        self.host.block()
+    }
+
+    fn tx(&self) -> &TxEnv {
+        self.host.tx()
+    }
+}
+
+/// Cost of `base` plus `word` per 32-byte word of input.
+#[inline]
+fn linear_gas(input: &[u8], base: u64, word: u64) -> u64 {
+    base + word * ((input.len() as u64 + 31) / 32)
+}
+
+/// Copies `input` into a buffer of `len` bytes, padding it with zeros or
+/// cutting it short.
+fn padded(input: &[u8], len: usize) -> Vec<u8> {
+    read_padded(input, 0, len)
+}
+
+/// Reads `len` bytes at `offset`, reading zeros past the end of `input`.
+fn read_padded(input: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; len];
+    if offset < input.len() {
+        let n = (input.len() - offset).min(len);
+        buf[..n].copy_from_slice(&input[offset..offset + n]);
+    }
+    buf
+}
+
+/// 0x01: recovers the address that signed a message hash.
+pub struct EcRecover;
+
+/// Order of the secp256k1 group. Signatures with r or s outside [1, n) are
+/// rejected.
+const SECP256K1_N: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+impl Precompile for EcRecover {
+    fn required_gas(&self, _input: &[u8]) -> u64 {
+        3000
+    }
+
+    /// Invalid signatures are not an error; they return no data.
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        let input = padded(input, 128);
+        let v = U256::from(&input[32..64]);
+        let r = U256::from(&input[64..96]);
+        let s = U256::from(&input[96..128]);
+        let n = U256::from(&SECP256K1_N[..]);
+        if (v != U256::from(27) && v != U256::from(28))
+            || r.is_zero()
+            || s.is_zero()
+            || r >= n
+            || s >= n
+        {
+            return Ok(Vec::new());
+        }
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(&input[..32]);
+        let mut signature = [0u8; 64];
+        signature.copy_from_slice(&input[64..128]);
+        let recovery_id = match secp256k1::RecoveryId::parse(input[63] - 27) {
+            Ok(id) => id,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let message = secp256k1::Message::parse(&hash);
+        let signature = secp256k1::Signature::parse(&signature);
+        match secp256k1::recover(&message, &signature, &recovery_id) {
+            Ok(public) => {
+                let mut output = vec![0u8; 32];
+                let hash = common::keccak(&public.serialize()[1..]);
+                output[12..].copy_from_slice(&hash[12..]);
+                Ok(output)
+            }
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+/// 0x02: SHA-256.
+pub struct Sha256Hash;
+
+impl Precompile for Sha256Hash {
+    fn required_gas(&self, input: &[u8]) -> u64 {
+        linear_gas(input, 60, 12)
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        Ok(Sha256::digest(input).to_vec())
+    }
+}
+
+/// 0x03: RIPEMD-160, left-padded to 32 bytes.
+pub struct Ripemd160Hash;
+
+impl Precompile for Ripemd160Hash {
+    fn required_gas(&self, input: &[u8]) -> u64 {
+        linear_gas(input, 600, 120)
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        let mut output = vec![0u8; 32];
+        output[12..].copy_from_slice(&Ripemd160::digest(input));
+        Ok(output)
+    }
+}
+
+/// 0x04: returns its input.
+pub struct Identity;
+
+impl Precompile for Identity {
+    fn required_gas(&self, input: &[u8]) -> u64 {
+        linear_gas(input, 15, 3)
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        Ok(input.to_vec())
+    }
+}
+
+/// 0x05: modular exponentiation of arbitrary-length integers (EIP-198),
+/// repriced by EIP-2565.
+pub struct ModExp {
+    pub eip2565: bool,
+}
+
+/// Longest base, exponent or modulus accepted, in bytes. Inputs this long
+/// already cost far more gas than a block holds, so this only keeps an
+/// unpriced call from allocating without bound.
+const MODEXP_MAX_LEN: u64 = 1024 * 1024;
+
+impl ModExp {
+    /// Reads the base, exponent and modulus lengths from the header,
+    /// saturating lengths that do not fit in a u64.
+    fn lengths(input: &[u8]) -> (u64, u64, u64) {
+        let header = padded(input, 96);
+        let len = |word: &[u8]| {
+            let value = U256::from(word);
+            if value > U256::from(u64::max_value()) {
+                u64::max_value()
+            } else {
+                value.low_u64()
+            }
+        };
+        (len(&header[..32]), len(&header[32..64]), len(&header[64..96]))
+    }
+
+    fn mult_complexity(&self, x: u128) -> u128 {
+        if self.eip2565 {
+            let words = (x + 7) / 8;
+            words.saturating_mul(words)
+        } else if x <= 64 {
+            x * x
+        } else if x <= 1024 {
+            x * x / 4 + 96 * x - 3072
+        } else {
+            x.saturating_mul(x) / 16 + x.saturating_mul(480) - 199_680
+        }
+    }
+}
+
+impl Precompile for ModExp {
+    fn required_gas(&self, input: &[u8]) -> u64 {
+        let (base_len, exp_len, mod_len) = ModExp::lengths(input);
+        // The exponent's bit length after its first 32 bytes, plus the
+        // position of the highest bit within them.
+        let head = if base_len > input.len() as u64 {
+            vec![0u8; 32]
+        } else {
+            read_padded(input, 96 + base_len as usize, exp_len.min(32) as usize)
+        };
+        let head_bits = U256::from(&head[..]).bits() as u128;
+        let mut adjusted_exp_len = head_bits.saturating_sub(1);
+        if exp_len > 32 {
+            adjusted_exp_len += (exp_len as u128 - 32).saturating_mul(8);
+        }
+        let complexity = self.mult_complexity(u128::from(base_len.max(mod_len)));
+        let gas = complexity.saturating_mul(adjusted_exp_len.max(1));
+        let gas = if self.eip2565 { (gas / 3).max(200) } else { gas / 20 };
+        if gas > u128::from(u64::max_value()) {
+            u64::max_value()
+        } else {
+            gas as u64
+        }
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        let (base_len, exp_len, mod_len) = ModExp::lengths(input);
+        if mod_len == 0 {
+            return Ok(Vec::new());
+        }
+        if base_len > MODEXP_MAX_LEN || exp_len > MODEXP_MAX_LEN || mod_len > MODEXP_MAX_LEN {
+            return Err(VmError::PrecompileFailure);
+        }
+        let (base_len, exp_len, mod_len) = (base_len as usize, exp_len as usize, mod_len as usize);
+        let base = BigUint::from_bytes_be(&read_padded(input, 96, base_len));
+        let exponent = BigUint::from_bytes_be(&read_padded(input, 96 + base_len, exp_len));
+        let modulus = BigUint::from_bytes_be(&read_padded(input, 96 + base_len + exp_len, mod_len));
+        let mut output = vec![0u8; mod_len];
+        if modulus == BigUint::from(0u32) {
+            return Ok(output);
+        }
+        let result = base.modpow(&exponent, &modulus).to_bytes_be();
+        output[mod_len - result.len()..].copy_from_slice(&result);
+        Ok(output)
+    }
+}
+
+/// Reads a field element, which must be below the field modulus.
+fn read_fq(input: &[u8], offset: usize) -> Result<bn::Fq, VmError> {
+    bn::Fq::from_slice(&input[offset..offset + 32]).map_err(|_| VmError::PrecompileFailure)
+}
+
+/// Reads a G1 point at `offset` in `input`, which must be on the curve.
+/// (0, 0) stands for the point at infinity.
+fn read_g1(input: &[u8], offset: usize) -> Result<bn::G1, VmError> {
+    let x = read_fq(input, offset)?;
+    let y = read_fq(input, offset + 32)?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(bn::G1::zero());
+    }
+    bn::AffineG1::new(x, y)
+        .map(Into::into)
+        .map_err(|_| VmError::PrecompileFailure)
+}
+
+/// Reads a G2 point at `offset` in `input`. Coordinates in Fq2 are encoded
+/// with the imaginary part first.
+fn read_g2(input: &[u8], offset: usize) -> Result<bn::G2, VmError> {
+    let x = bn::Fq2::new(read_fq(input, offset + 32)?, read_fq(input, offset)?);
+    let y = bn::Fq2::new(read_fq(input, offset + 96)?, read_fq(input, offset + 64)?);
+    if x.is_zero() && y.is_zero() {
+        return Ok(bn::G2::zero());
+    }
+    bn::AffineG2::new(x, y)
+        .map(Into::into)
+        .map_err(|_| VmError::PrecompileFailure)
+}
+
+fn write_g1(point: bn::G1) -> Vec<u8> {
+    let mut output = vec![0u8; 64];
+    if let Some(point) = bn::AffineG1::from_jacobian(point) {
+        // Both coordinates are field elements, which always fit.
+        point.x().to_big_endian(&mut output[..32]).unwrap();
+        point.y().to_big_endian(&mut output[32..]).unwrap();
+    }
+    output
+}
+
+/// 0x06: point addition on alt_bn128 (EIP-196).
+pub struct Bn128Add {
+    pub gas: u64,
+}
+
+impl Precompile for Bn128Add {
+    fn required_gas(&self, _input: &[u8]) -> u64 {
+        self.gas
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        let input = padded(input, 128);
+        let p = read_g1(&input, 0)?;
+        let q = read_g1(&input, 64)?;
+        Ok(write_g1(p + q))
+    }
+}
+
+/// 0x07: scalar multiplication on alt_bn128 (EIP-196).
+pub struct Bn128Mul {
+    pub gas: u64,
+}
+
+impl Precompile for Bn128Mul {
+    fn required_gas(&self, _input: &[u8]) -> u64 {
+        self.gas
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        let input = padded(input, 96);
+        let p = read_g1(&input, 0)?;
+        let scalar = bn::Fr::from_slice(&input[64..96]).map_err(|_| VmError::PrecompileFailure)?;
+        Ok(write_g1(p * scalar))
+    }
+}
+
+/// 0x08: pairing check on alt_bn128 (EIP-197). Returns 1 if the product of
+/// the pairings of all (G1, G2) pairs is one.
+pub struct Bn128Pairing {
+    pub gas_base: u64,
+    pub gas_pair: u64,
+}
+
+impl Precompile for Bn128Pairing {
+    fn required_gas(&self, input: &[u8]) -> u64 {
+        self.gas_base + self.gas_pair * (input.len() / 192) as u64
+    }
+
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        if input.len() % 192 != 0 {
+            return Err(VmError::PrecompileFailure);
+        }
+        let mut pairs = Vec::with_capacity(input.len() / 192);
+        for offset in (0..input.len()).step_by(192) {
+            pairs.push((read_g1(input, offset)?, read_g2(input, offset + 64)?));
+        }
+        let mut output = vec![0u8; 32];
+        if pairs.is_empty() || bn::pairing_batch(&pairs) == bn::Gt::one() {
+            output[31] = 1;
+        }
+        Ok(output)
+    }
+}
+
+/// 0x09: the BLAKE2b compression function F (EIP-152).
+pub struct Blake2F;
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09_e667_f3bc_c908,
+    0xbb67_ae85_84ca_a73b,
+    0x3c6e_f372_fe94_f82b,
+    0xa54f_f53a_5f1d_36f1,
+    0x510e_527f_ade6_82d1,
+    0x9b05_688c_2b3e_6c1f,
+    0x1f83_d9ab_fb41_bd6b,
+    0x5be0_cd19_137e_2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+#[inline]
+fn blake2b_g(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+fn blake2b_f(rounds: u32, h: &mut [u64; 8], m: &[u64; 16], t: [u64; 2], last: bool) {
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if last {
+        v[14] = !v[14];
+    }
+    for i in 0..rounds as usize {
+        let s = &BLAKE2B_SIGMA[i % 10];
+        blake2b_g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        blake2b_g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        blake2b_g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        blake2b_g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        blake2b_g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        blake2b_g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        blake2b_g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        blake2b_g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+fn read_u64_le(input: &[u8], offset: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&input[offset..offset + 8]);
+    u64::from_le_bytes(buf)
+}
+
+impl Precompile for Blake2F {
+    /// One gas per round.
+    fn required_gas(&self, input: &[u8]) -> u64 {
+        if input.len() < 4 {
+            return 0;
+        }
+        u64::from(u32::from_be_bytes([input[0], input[1], input[2], input[3]]))
+    }
+
+    /// Input is rounds (4 bytes, big endian), the state h (8 words), the
+    /// message m (16 words), the offset counter t (2 words) and the final
+    /// block flag f (1 byte). Words are little endian.
+    fn run(&self, input: &[u8]) -> Result<Vec<u8>, VmError> {
+        if input.len() != 213 || input[212] > 1 {
+            return Err(VmError::PrecompileFailure);
+        }
+        let rounds = u32::from_be_bytes([input[0], input[1], input[2], input[3]]);
+        let mut h = [0u64; 8];
+        for (i, word) in h.iter_mut().enumerate() {
+            *word = read_u64_le(input, 4 + i * 8);
+        }
+        let mut m = [0u64; 16];
+        for (i, word) in m.iter_mut().enumerate() {
+            *word = read_u64_le(input, 68 + i * 8);
+        }
+        let t = [read_u64_le(input, 196), read_u64_le(input, 204)];
+        blake2b_f(rounds, &mut h, &m, t, input[212] == 1);
+        let mut output = Vec::with_capacity(64);
+        for word in &h {
+            output.extend_from_slice(&word.to_le_bytes());
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::hex;
+
+    #[test]
+    fn test_ecrecover() {
+        // Vector from the go-ethereum precompile tests.
+        let input = hex(concat!(
+            "38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e",
+            "000000000000000000000000000000000000000000000000000000000000001b",
+            "38d18acb67d25c8bb9942764b62f18e17054f66a817bd4295423adf9ed98873e",
+            "789d1dd423d25f0772d2748d60f7e4b81bb14d086eba8e8e8efb6dcff8a4ae02",
+        ));
+        assert_eq!(EcRecover.required_gas(&input), 3000);
+        assert_eq!(
+            EcRecover.run(&input),
+            Ok(hex("000000000000000000000000ceaccac640adf55b2028469bd36ba501f28b699d"))
+        );
+
+        // v must be exactly 27 or 28, with no stray high bytes.
+        let mut bad_v = input.clone();
+        bad_v[63] = 29;
+        assert_eq!(EcRecover.run(&bad_v), Ok(Vec::new()));
+        let mut bad_v = input.clone();
+        bad_v[32] = 1;
+        assert_eq!(EcRecover.run(&bad_v), Ok(Vec::new()));
+        // s >= n.
+        let mut bad_s = input.clone();
+        bad_s[96..].copy_from_slice(&SECP256K1_N);
+        assert_eq!(EcRecover.run(&bad_s), Ok(Vec::new()));
+        assert_eq!(EcRecover.run(&[]), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(Sha256Hash.required_gas(&[]), 60);
+        assert_eq!(Sha256Hash.required_gas(&[0; 33]), 84);
+        assert_eq!(
+            Sha256Hash.run(&[]),
+            Ok(hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"))
+        );
+        assert_eq!(
+            Sha256Hash.run(b"abc"),
+            Ok(hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"))
+        );
+    }
+
+    #[test]
+    fn test_ripemd160() {
+        assert_eq!(Ripemd160Hash.required_gas(&[0; 32]), 720);
+        assert_eq!(
+            Ripemd160Hash.run(&[]),
+            Ok(hex("0000000000000000000000009c1185a5c5e9fc54612808977ee8f548b2258d31"))
+        );
+        assert_eq!(
+            Ripemd160Hash.run(b"abc"),
+            Ok(hex("0000000000000000000000008eb208f7e05d987a9b044a8e98c6b087f15a0bfc"))
+        );
+    }
+
+    #[test]
+    fn test_identity() {
+        assert_eq!(Identity.required_gas(&[]), 15);
+        assert_eq!(Identity.required_gas(&[0; 64]), 21);
+        assert_eq!(Identity.run(&[1, 2, 3]), Ok(vec![1, 2, 3]));
+    }
+
+    /// Examples from EIP-198.
+    const MODEXP_EXAMPLE_1: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000020",
+        "0000000000000000000000000000000000000000000000000000000000000020",
+        "03",
+        "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+        "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+    );
+    const MODEXP_EXAMPLE_2: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000000",
+        "0000000000000000000000000000000000000000000000000000000000000020",
+        "0000000000000000000000000000000000000000000000000000000000000020",
+        "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2e",
+        "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+    );
+
+    #[test]
+    fn test_modexp() {
+        let byzantium = ModExp { eip2565: false };
+        let berlin = ModExp { eip2565: true };
+        let input = hex(MODEXP_EXAMPLE_1);
+        assert_eq!(byzantium.required_gas(&input), 13056);
+        assert_eq!(berlin.required_gas(&input), 1360);
+        let mut one = vec![0u8; 32];
+        one[31] = 1;
+        assert_eq!(berlin.run(&input), Ok(one));
+
+        // 0 ^ x mod p, with the base omitted.
+        let input = hex(MODEXP_EXAMPLE_2);
+        assert_eq!(berlin.run(&input), Ok(vec![0u8; 32]));
+        // A zero modulus gives zero rather than failing.
+        let mut input = hex(MODEXP_EXAMPLE_1);
+        input.truncate(96 + 1 + 32);
+        assert_eq!(berlin.run(&input), Ok(vec![0u8; 32]));
+        assert_eq!(berlin.required_gas(&[]), 200);
+        assert_eq!(byzantium.required_gas(&[]), 0);
+        assert_eq!(berlin.run(&[]), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_modexp_oversize() {
+        // A 2^255 byte base is priced out of reach and never allocated.
+        let mut input = vec![0u8; 96];
+        input[0] = 0x80;
+        input[95] = 1;
+        let modexp = ModExp { eip2565: true };
+        assert_eq!(modexp.required_gas(&input), u64::max_value());
+        assert_eq!(modexp.run(&input), Err(VmError::PrecompileFailure));
+        // Lengths are irrelevant when there is no modulus.
+        input[95] = 0;
+        assert_eq!(modexp.run(&input), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_precompiles_by_fork() {
+        let five = Address::from_low_u64_be(5);
+        assert!(!Precompiles::with_fork(Fork::SpuriousDragon).contains(&five));
+        assert!(Precompiles::with_fork(Fork::Byzantium).contains(&five));
+        let nine = Address::from_low_u64_be(9);
+        assert!(!Precompiles::with_fork(Fork::Petersburg).contains(&nine));
+        assert!(Precompiles::with_fork(Fork::Istanbul).contains(&nine));
+        let add = Address::from_low_u64_be(6);
+        assert_eq!(Precompiles::with_fork(Fork::Byzantium).required_gas(&add, &[]), 500);
+        assert_eq!(Precompiles::with_fork(Fork::Istanbul).required_gas(&add, &[]), 150);
+    }
+
+    const G1: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000002",
+    );
+    const G1_DOUBLE: &str = concat!(
+        "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3",
+        "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4",
+    );
+    const G1_NEG: &str = concat!(
+        "0000000000000000000000000000000000000000000000000000000000000001",
+        "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd45",
+    );
+    const G2: &str = concat!(
+        "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2",
+        "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed",
+        "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b",
+        "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa",
+    );
+
+    #[test]
+    fn test_bn128_add() {
+        let add = Bn128Add { gas: 150 };
+        assert_eq!(add.run(&hex(&[G1, G1].concat())), Ok(hex(G1_DOUBLE)));
+        // P + -P is the point at infinity.
+        assert_eq!(add.run(&hex(&[G1, G1_NEG].concat())), Ok(vec![0u8; 64]));
+        assert_eq!(add.run(&[]), Ok(vec![0u8; 64]));
+        // (1, 3) is not on the curve.
+        let mut bad = hex(G1);
+        bad[63] = 3;
+        assert_eq!(add.run(&bad), Err(VmError::PrecompileFailure));
+        // Coordinates must be below the field modulus.
+        let mut bad = hex(G1);
+        bad[..32].copy_from_slice(&[0xff; 32]);
+        assert_eq!(add.run(&bad), Err(VmError::PrecompileFailure));
+    }
+
+    #[test]
+    fn test_bn128_mul() {
+        let mul = Bn128Mul { gas: 6000 };
+        let mut input = hex(G1);
+        input.extend_from_slice(&[0u8; 31]);
+        input.push(2);
+        assert_eq!(mul.run(&input), Ok(hex(G1_DOUBLE)));
+        // Multiplying by the group order gives the point at infinity.
+        let order = hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001");
+        let mut input = hex(G1);
+        input.extend_from_slice(&order);
+        assert_eq!(mul.run(&input), Ok(vec![0u8; 64]));
+    }
+
+    #[test]
+    fn test_bn128_pairing() {
+        let pairing = Bn128Pairing {
+            gas_base: 45000,
+            gas_pair: 34000,
+        };
+        let one = {
+            let mut one = vec![0u8; 32];
+            one[31] = 1;
+            one
+        };
+        assert_eq!(pairing.run(&[]), Ok(one.clone()));
+        // e(P, Q) * e(-P, Q) == 1, while e(P, Q) alone is not.
+        let input = hex(&[G1, G2, G1_NEG, G2].concat());
+        assert_eq!(pairing.required_gas(&input), 45000 + 2 * 34000);
+        assert_eq!(pairing.run(&input), Ok(one));
+        assert_eq!(pairing.run(&hex(&[G1, G2].concat())), Ok(vec![0u8; 32]));
+        assert_eq!(pairing.run(&hex(G1)), Err(VmError::PrecompileFailure));
+        // G2 coordinates swapped are not on the twist.
+        let swapped = hex(&[G1, &G2[64..128], &G2[..64], &G2[128..]].concat());
+        assert_eq!(pairing.run(&swapped), Err(VmError::PrecompileFailure));
+    }
+
+    #[test]
+    fn test_blake2f() {
+        // Vector 5 from EIP-152, the compression of "abc".
+        let input = hex(concat!(
+            "0000000c",
+            "48c9bdf267e6096a3ba7ca8485ae67bb2bf894fe72f36e3cf1361d5f3af54fa5",
+            "d182e6ad7f520e511f6c3e2b8c68059b6bbd41fbabd9831f79217e1319cde05b",
+            "6162630000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0300000000000000",
+            "0000000000000000",
+            "01",
+        ));
+        assert_eq!(Blake2F.required_gas(&input), 12);
+        assert_eq!(
+            Blake2F.run(&input),
+            Ok(hex(concat!(
+                "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1",
+                "7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+            )))
+        );
+        assert_eq!(Blake2F.run(&input[1..]), Err(VmError::PrecompileFailure));
+        let mut bad_flag = input.clone();
+        bad_flag[212] = 2;
+        assert_eq!(Blake2F.run(&bad_flag), Err(VmError::PrecompileFailure));
+    }
+}
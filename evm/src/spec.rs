@@ -0,0 +1,63 @@
+use super::opcodes::OpCode;
+
+/// Ethereum hard forks. Each fork includes the rules of all forks before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Fork {
+    Frontier,
+    Homestead,
+    /// EIP-150.
+    TangerineWhistle,
+    /// EIP-155, EIP-160, EIP-161 and EIP-170.
+    SpuriousDragon,
+    Byzantium,
+    /// Treated like Petersburg, i.e. without the EIP-1283 SSTORE metering.
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Shanghai,
+    Cancun,
+}
+
+impl Fork {
+    /// Whether `op` is defined under this fork. Executing an opcode that is
+    /// not yet enabled halts like any undefined byte.
+    pub fn has_opcode(self, op: OpCode) -> bool {
+        match op {
+            OpCode::DELEGATECALL => self >= Fork::Homestead,
+            OpCode::REVERT
+            | OpCode::RETURNDATASIZE
+            | OpCode::RETURNDATACOPY
+            | OpCode::STATICCALL => self >= Fork::Byzantium,
+            OpCode::SHL
+            | OpCode::SHR
+            | OpCode::SAR
+            | OpCode::CREATE2
+            | OpCode::EXTCODEHASH => self >= Fork::Constantinople,
+            OpCode::CHAINID | OpCode::SELFBALANCE => self >= Fork::Istanbul,
+            OpCode::BASEFEE => self >= Fork::London,
+            OpCode::PUSH0 => self >= Fork::Shanghai,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_fork_has_opcode() {
+        assert!(!Fork::Frontier.has_opcode(OpCode::DELEGATECALL));
+        assert!(Fork::Homestead.has_opcode(OpCode::DELEGATECALL));
+        assert!(!Fork::Byzantium.has_opcode(OpCode::SHL));
+        assert!(Fork::Constantinople.has_opcode(OpCode::CREATE2));
+        assert!(!Fork::Petersburg.has_opcode(OpCode::CHAINID));
+        assert!(Fork::Istanbul.has_opcode(OpCode::SELFBALANCE));
+        assert!(!Fork::Berlin.has_opcode(OpCode::BASEFEE));
+        assert!(Fork::London.has_opcode(OpCode::BASEFEE));
+        assert!(!Fork::London.has_opcode(OpCode::PUSH0));
+        assert!(Fork::Cancun.has_opcode(OpCode::PUSH0));
+        assert!(Fork::Frontier.has_opcode(OpCode::ADD));
+    }
+}
@@ -1,3 +1,10 @@
+use super::err::VmError;
+
+/// Maximum number of items on the EVM stack.
+pub const STACK_LIMIT: usize = 1024;
+
+/// Items are addressed from the top of the stack: `back(0)` is the top, while
+/// `dup(n)` and `swap(n)` count the top as the first item.
 pub struct Stack<T> {
     data: Vec<T>,
 }
@@ -7,6 +14,8 @@ impl<T: Clone + Copy> Stack<T> {
         Stack { data: Vec::new() }
     }
 
+    /// Pre-allocates room for `capacity` items. The stack is still limited to
+    /// `STACK_LIMIT` items.
     pub fn with_capacity(capacity: usize) -> Self {
         Stack {
             data: Vec::with_capacity(capacity),
@@ -17,43 +26,76 @@ impl<T: Clone + Copy> Stack<T> {
         &self.data
     }
 
-    pub fn push(&mut self, d: T) {
-        self.data.push(d)
+    pub fn push(&mut self, d: T) -> Result<(), VmError> {
+        if self.data.len() >= STACK_LIMIT {
+            return Err(VmError::StackOverflow);
+        }
+        self.data.push(d);
+        Ok(())
     }
 
-    pub fn push_n(&mut self, ds: &[T]) {
-        self.data.extend_from_slice(ds)
+    pub fn push_n(&mut self, ds: &[T]) -> Result<(), VmError> {
+        if self.data.len() + ds.len() > STACK_LIMIT {
+            return Err(VmError::StackOverflow);
+        }
+        self.data.extend_from_slice(ds);
+        Ok(())
     }
 
-    pub fn pop(&mut self) -> T {
-        self.data.pop().unwrap()
+    pub fn pop(&mut self) -> Result<T, VmError> {
+        self.data.pop().ok_or(VmError::StackUnderflow)
     }
 
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
-    pub fn swap(&mut self, n: usize) {
+    /// Exchanges the top item with the nth item, so SWAP1 is `swap(2)`.
+    pub fn swap(&mut self, n: usize) -> Result<(), VmError> {
+        if n == 0 || !self.require(n) {
+            return Err(VmError::StackUnderflow);
+        }
         let len = self.data.len();
-        self.data.swap(len - n, len - 1)
+        self.data.swap(len - n, len - 1);
+        Ok(())
     }
 
-    pub fn dup(&mut self, n: usize) {
-        let d = self.back(n - 1);
-        self.data.push(d)
+    /// Pushes a copy of the nth item, so DUP1 is `dup(1)`.
+    pub fn dup(&mut self, n: usize) -> Result<(), VmError> {
+        if n == 0 {
+            return Err(VmError::StackUnderflow);
+        }
+        let d = self.back(n - 1)?;
+        self.push(d)
     }
 
-    pub fn back(&self, n: usize) -> T {
-        self.data[self.data.len() - n - 1].clone()
+    pub fn back(&self, n: usize) -> Result<T, VmError> {
+        if !self.require(n + 1) {
+            return Err(VmError::StackUnderflow);
+        }
+        Ok(self.data[self.data.len() - n - 1])
     }
 
-    pub fn peek(&self) -> T {
+    pub fn peek(&self) -> Result<T, VmError> {
         self.back(0)
     }
 
+    /// Whether the stack holds at least `n` items.
     pub fn require(&self, n: usize) -> bool {
         self.data.len() >= n
     }
+
+    /// Whether `inputs` items can be popped and `outputs` items pushed
+    /// afterwards.
+    pub fn check(&self, inputs: usize, outputs: usize) -> Result<(), VmError> {
+        if !self.require(inputs) {
+            return Err(VmError::StackUnderflow);
+        }
+        if self.data.len() - inputs + outputs > STACK_LIMIT {
+            return Err(VmError::StackOverflow);
+        }
+        Ok(())
+    }
 }
 
 
@@ -64,10 +106,10 @@ mod tests {
     fn test_stack_with_capacity() {
         let mut st: Stack<u32> = Stack::with_capacity(2);
         assert_eq!(st.len(), 0);
-        st.push(0x01);
-        st.push(0x02);
+        st.push(0x01).unwrap();
+        st.push(0x02).unwrap();
         assert_eq!(st.len(), 2);
-        st.push(0x03);
+        st.push(0x03).unwrap();
         assert_eq!(st.len(), 3);
     }
 
@@ -75,79 +117,91 @@ mod tests {
     fn test_stack_new() {
         let mut st: Stack<u32> = Stack::new();
         assert_eq!(st.len(), 0);
-        st.push(0x01);
-        st.push(0x02);
+        st.push(0x01).unwrap();
+        st.push(0x02).unwrap();
         assert_eq!(st.len(), 2);
-        st.push(0x03);
+        st.push(0x03).unwrap();
         assert_eq!(st.len(), 3);
     }
 
     #[test]
     fn test_stack_back() {
         let mut st: Stack<u32> = Stack::new();
-        st.push(0x01);
-        st.push(0x02);
-        assert_eq!(st.back(0), 0x02);
-        assert_eq!(st.back(1), 0x01);
+        st.push(0x01).unwrap();
+        st.push(0x02).unwrap();
+        assert_eq!(st.back(0), Ok(0x02));
+        assert_eq!(st.back(1), Ok(0x01));
     }
 
     #[test]
-    #[should_panic]
     fn test_stack_back_neg() {
         let mut st: Stack<u32> = Stack::new();
-        st.push(0x01);
-        st.push(0x02);
-        st.back(2);
+        st.push(0x01).unwrap();
+        st.push(0x02).unwrap();
+        assert_eq!(st.back(2), Err(VmError::StackUnderflow));
     }
 
     #[test]
     fn test_stack_push_n() {
         let mut st: Stack<u32> = Stack::new();
         let ls = vec![0x01, 0x02];
-        st.push_n(&ls);
-        assert_eq!(st.back(0), 0x02);
-        assert_eq!(st.back(1), 0x01);
+        st.push_n(&ls).unwrap();
+        assert_eq!(st.back(0), Ok(0x02));
+        assert_eq!(st.back(1), Ok(0x01));
     }
 
     #[test]
     fn test_stack_pop() {
         let mut st: Stack<u32> = Stack::new();
         let ls = vec![0x01, 0x02];
-        st.push_n(&ls);
-        assert_eq!(st.pop(), 0x02);
-        assert_eq!(st.pop(), 0x01);
+        st.push_n(&ls).unwrap();
+        assert_eq!(st.pop(), Ok(0x02));
+        assert_eq!(st.pop(), Ok(0x01));
     }
 
     #[test]
-    #[should_panic]
     fn test_stack_pop_neg() {
         let mut st: Stack<u32> = Stack::new();
         let ls = vec![0x01, 0x02];
-        st.push_n(&ls);
-        for i in 0..3 {
-            println!("{}", i);
-            st.pop();
-        }
+        st.push_n(&ls).unwrap();
+        assert!(st.pop().is_ok());
+        assert!(st.pop().is_ok());
+        assert_eq!(st.pop(), Err(VmError::StackUnderflow));
     }
 
     #[test]
     fn test_stack_swap() {
         let mut st: Stack<u32> = Stack::new();
         let ls = vec![0x01, 0x02, 0x03, 0x04];
-        st.push_n(&ls);
-        st.swap(2);
-        assert_eq!(st.back(0), 0x03);
-        assert_eq!(st.back(1), 0x04);
+        st.push_n(&ls).unwrap();
+        st.swap(2).unwrap();
+        assert_eq!(st.back(0), Ok(0x03));
+        assert_eq!(st.back(1), Ok(0x04));
+        assert_eq!(st.swap(5), Err(VmError::StackUnderflow));
     }
 
     #[test]
     fn test_stack_dup() {
         let mut st: Stack<u32> = Stack::new();
         let ls = vec![0x01, 0x02];
-        st.push_n(&ls);
-        st.dup(1);
-        assert_eq!(st.back(0), 0x02);
-        st.dup(3);
-        assert_eq!(st.back(0), 0x01);
+        st.push_n(&ls).unwrap();
+        st.dup(1).unwrap();
+        assert_eq!(st.back(0), Ok(0x02));
+        st.dup(3).unwrap();
+        assert_eq!(st.back(0), Ok(0x01));
+        assert_eq!(st.dup(5), Err(VmError::StackUnderflow));
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        let mut st: Stack<u32> = Stack::with_capacity(STACK_LIMIT);
+        for i in 0..STACK_LIMIT {
+            st.push(i as u32).unwrap();
+        }
+        assert_eq!(st.push(0x00), Err(VmError::StackOverflow));
+        assert_eq!(st.dup(1), Err(VmError::StackOverflow));
+        assert_eq!(st.check(1, 1), Ok(()));
+        assert_eq!(st.check(1, 2), Err(VmError::StackOverflow));
+        assert_eq!(st.len(), STACK_LIMIT);
     }
 }
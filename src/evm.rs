@@ -2,24 +2,28 @@ use ethereum_types::{Address, H256, U256};
 
 use super::interpreter::memory::Memory;
 use super::interpreter::stack::Stack;
-use super::statedb::statedb::StateDB;
+use super::statedb::statedb::{Log, StateDB};
+use evm::err::VmError;
+use evm::host::{self, BlockEnv, BlockHashProvider, Host, TxEnv};
 
 pub struct EVMContext {
     pub stack: Stack<U256>,
     pub memory: Memory,
     pub state_db: Box<dyn StateDB>,
 
-    pub fn_get_hash: fn(num: U256) -> H256,
+    pub block_hashes: Box<dyn BlockHashProvider>,
 
     pub return_data: Vec<u8>,
 
     pub info: EVMInfo,
 }
 
+/// The block and transaction being executed. The quota price is the
+/// transaction gas price; GASLIMIT reads `block.gas_limit`, not the quota.
 pub struct EVMInfo {
-    pub origin: Address,
-    pub block_number: U256,
-    pub quota_price: U256,
+    pub block: BlockEnv,
+    pub tx: TxEnv,
+    /// Quota the transaction may use, kept apart from the block gas limit.
     pub quota_limit: u64,
 }
 
@@ -36,3 +40,121 @@ pub struct Contract {
     pub quota: u64,
     pub value: U256,
 }
+
+/// Lets the shared interpreter run against the trie-backed state.
+impl Host for EVMContext {
+    fn get_balance(&self, address: Address) -> U256 {
+        self.state_db.get_balance(address)
+    }
+
+    fn get_code(&self, address: Address) -> Vec<u8> {
+        self.state_db.get_code(address)
+    }
+
+    fn get_code_hash(&self, address: Address) -> H256 {
+        self.state_db.get_code_hash(address)
+    }
+
+    fn get_code_size(&self, address: Address) -> usize {
+        self.state_db.get_code_size(address)
+    }
+
+    fn set_code(&mut self, address: Address, code: &[u8]) {
+        self.state_db.set_code(address, code)
+    }
+
+    fn get_nonce(&self, address: Address) -> Result<U256, VmError> {
+        U256::from_dec_str(&self.state_db.get_nonce(address)).map_err(|_| VmError::InvalidNonce)
+    }
+
+    fn set_nonce(&mut self, address: Address, nonce: U256) {
+        self.state_db.set_nonce(address, nonce.to_string())
+    }
+
+    fn create_account(&mut self, address: Address) {
+        self.state_db.create_account(address)
+    }
+
+    fn suicide(&mut self, address: Address) -> bool {
+        self.state_db.suicide(address)
+    }
+
+    fn has_suicided(&self, address: Address) -> bool {
+        self.state_db.has_suicided(address)
+    }
+
+    fn add_balance(&mut self, address: Address, value: U256) {
+        self.state_db.add_balance(address, value)
+    }
+
+    fn sub_balance(&mut self, address: Address, value: U256) {
+        self.state_db.sub_balance(address, value)
+    }
+
+    fn exists(&self, address: Address) -> bool {
+        self.state_db.exists(address)
+    }
+
+    fn empty(&self, address: Address) -> bool {
+        self.state_db.empty(address)
+    }
+
+    fn get_state(&self, address: Address, key: H256) -> H256 {
+        self.state_db.get_state(address, key)
+    }
+
+    fn set_state(&mut self, address: Address, key: H256, value: H256) {
+        self.state_db.set_state(address, key, value)
+    }
+
+    fn get_committed_state(&self, address: Address, key: H256) -> H256 {
+        self.state_db.get_committed_state(address, key)
+    }
+
+    fn add_refund(&mut self, gas: u64) {
+        self.state_db.add_refund(gas)
+    }
+
+    fn sub_refund(&mut self, gas: u64) {
+        self.state_db.sub_refund(gas)
+    }
+
+    fn get_refund(&self) -> i64 {
+        self.state_db.get_refund()
+    }
+
+    fn get_block_hash(&self, number: U256) -> H256 {
+        self.block_hashes.block_hash(number)
+    }
+
+    fn add_log(&mut self, log: host::Log) {
+        self.state_db.add_log(Log {
+            address: log.address,
+            topics: log.topics,
+            data: log.data,
+            block_number: self.info.block.number.low_u64(),
+            tx_hash: self.info.tx.hash,
+            removed: false,
+        })
+    }
+
+    fn snapshot(&mut self) -> usize {
+        self.state_db.snapshot()
+    }
+
+    fn revert_to_snapshot(&mut self, id: usize) {
+        self.state_db.revert_to_snapshot(id)
+    }
+
+    fn discard_snapshot(&mut self, id: usize) {
+        self.state_db.discard_snapshot(id)
+    }
+
+    fn block(&self) -> &BlockEnv {
+        &self.info.block
+    }
+
+    fn tx(&self) -> &TxEnv {
+        &self.info.tx
+    }
+}
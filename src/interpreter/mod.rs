@@ -1,2 +1,2 @@
-pub mod memory;
-pub mod stack;
+pub use evm::memory;
+pub use evm::stack;
@@ -1,5 +1,3 @@
-#![feature(vec_resize_default)]
-
 pub mod common;
 pub mod evm;
 pub mod instructions;
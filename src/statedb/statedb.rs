@@ -17,8 +17,9 @@ pub trait StateDB {
     fn get_code_size(&self, address: Address) -> usize;
 
     fn add_refund(&mut self, quota: u64);
-    fn set_refund(&mut self, quota: u64);
-    fn get_refund(&self) -> u64;
+    fn sub_refund(&mut self, quota: u64);
+    /// May be negative while a transaction runs.
+    fn get_refund(&self) -> i64;
 
     fn get_committed_state(&self, address: Address, hash: H256) -> H256;
     fn get_state(&self, address: Address, key: H256) -> H256;
@@ -32,6 +33,7 @@ pub trait StateDB {
 
     fn revert_to_snapshot(&mut self, id: usize);
     fn snapshot(&mut self) -> usize;
+    fn discard_snapshot(&mut self, id: usize);
 
     fn add_log(&mut self, log: Log);
     fn add_preimage(&mut self, hash: H256, preimage: &[u8]);